@@ -1,116 +1,650 @@
 use core::fmt::Display;
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
 
-use super::token::{Token, TokenType};
+use super::class::{LoxClass, LoxInstance};
+use super::environment::Environment;
+use super::function::LoxFunction;
+use super::lox::RuntimeError;
+use super::token::{Span, Token, TokenType};
+use super::visitor::{walk_expr, walk_stmt, ExprVisitor, StmtVisitor};
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Object {
+    Integer(i64),
     Number(f64),
     String(String),
     Boolean(bool),
+    Function(Rc<LoxFunction>),
+    Class(Rc<LoxClass>),
+    Instance(Rc<RefCell<LoxInstance>>),
     Nil,
 }
 
+/// How many scopes up a variable reference resolves, filled in by the
+/// resolver. `None` means the variable is looked up in the global scope.
+pub type Depth = Cell<Option<usize>>;
+
 #[derive(Debug, Clone)]
 pub enum Expr {
+    Assign(Token, Box<Expr>, Depth),
     Binary(Box<Expr>, Token, Box<Expr>),
+    Call(Box<Expr>, Token, Vec<Expr>),
+    Get(Box<Expr>, Token),
     Grouping(Box<Expr>),
-    Literal(Object),
+    Literal(Object, Span),
+    Logical(Box<Expr>, Token, Box<Expr>),
+    Set(Box<Expr>, Token, Box<Expr>),
+    /// The printed form of an expression interpolated into a string.
+    Stringify(Box<Expr>),
+    Super(Token, Depth),
+    This(Token, Depth),
     Unary(Token, Box<Expr>),
+    Variable(Token, Depth),
 }
 
-pub fn visit(expr: Expr) -> String {
-    format!("{}", expr)
+impl Expr {
+    /// The source covered by this expression, used to point diagnostics at it.
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Assign(name, value, _) => name.span.to(value.span()),
+            Expr::Binary(lhs, _, rhs) | Expr::Logical(lhs, _, rhs) => lhs.span().to(rhs.span()),
+            Expr::Call(callee, paren, _) => callee.span().to(paren.span),
+            Expr::Get(object, name) => object.span().to(name.span),
+            Expr::Grouping(expr) => expr.span(),
+            Expr::Literal(_, span) => *span,
+            Expr::Set(object, _, value) => object.span().to(value.span()),
+            Expr::Stringify(expr) => expr.span(),
+            Expr::Super(method, _) => method.span,
+            Expr::This(keyword, _) => keyword.span,
+            Expr::Unary(op, rhs) => op.span.to(rhs.span()),
+            Expr::Variable(name, _) => name.span,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Stmt {
+    Block(Vec<Stmt>),
+    Class(Token, Option<Box<Expr>>, Vec<Rc<FunctionDecl>>),
+    Expression(Box<Expr>),
+    Function(Rc<FunctionDecl>),
+    If(Box<Expr>, Box<Stmt>, Option<Box<Stmt>>),
+    Print(Box<Expr>),
+    Return(Token, Option<Box<Expr>>),
+    Var(Token, Option<Box<Expr>>),
+    While(Box<Expr>, Box<Stmt>),
+}
+
+#[derive(Clone, Debug)]
+pub struct FunctionDecl {
+    pub name: Token,
+    pub params: Vec<Token>,
+    pub body: Vec<Stmt>,
 }
 
 impl Display for Object {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Object::Nil => write!(f, "{}", "nil"),
+            Object::Nil => write!(f, "nil"),
+            Object::Integer(num) => write!(f, "{}", num),
             Object::Number(num) => write!(f, "{}", num),
             Object::String(string) => write!(f, "{}", string),
             Object::Boolean(bool) => write!(f, "{}", bool),
+            Object::Function(function) => write!(f, "{:?}", function),
+            Object::Class(class) => write!(f, "{:?}", class),
+            Object::Instance(instance) => write!(f, "{:?}", instance.borrow()),
         }
     }
 }
 
 impl Display for Expr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Expr::Binary(lhs, op, rhs) => {
-                write!(f, "({} {} {})", op.get_lexeme(), lhs, rhs)
-            }
-            Expr::Grouping(expr) => write!(f, "(group {})", expr),
-            Expr::Literal(obj) => write!(f, "{}", obj),
-            Expr::Unary(op, rhs) => write!(f, "({} {})", op.get_lexeme(), rhs),
+        write!(f, "{}", walk_expr(&mut AstPrinter, self))
+    }
+}
+
+/// Prints expressions as Lisp-style s-expressions, e.g. `(+ 1 (group 2))`.
+pub struct AstPrinter;
+
+impl AstPrinter {
+    fn parenthesize(&mut self, name: &str, exprs: &[&Expr]) -> String {
+        let mut out = format!("({}", name);
+        for expr in exprs {
+            out.push(' ');
+            out.push_str(&walk_expr(self, expr));
         }
+        out.push(')');
+        out
     }
 }
 
-pub fn eval(expr: Expr) -> Result<Object, Expr> {
-    match expr {
-        Expr::Unary(sign, e) => eval_unary(sign.clone(), *e),
-        Expr::Literal(obj) => Ok(obj),
-        Expr::Binary(lhs, op, rhs) => eval_binary(*lhs, op.clone(), *rhs),
-        Expr::Grouping(e) => eval_grouping(*e),
+impl ExprVisitor<String> for AstPrinter {
+    fn visit_assign(&mut self, name: &Token, value: &Expr, _: &Depth) -> String {
+        format!("(= {} {})", name.lexeme, walk_expr(self, value))
+    }
+
+    fn visit_binary(&mut self, lhs: &Expr, op: &Token, rhs: &Expr) -> String {
+        self.parenthesize(op.lexeme.as_str(), &[lhs, rhs])
+    }
+
+    fn visit_call(&mut self, callee: &Expr, _: &Token, arguments: &[Expr]) -> String {
+        let mut exprs = vec![callee];
+        exprs.extend(arguments);
+        self.parenthesize("call", &exprs)
+    }
+
+    fn visit_get(&mut self, object: &Expr, name: &Token) -> String {
+        format!("(. {} {})", walk_expr(self, object), name.lexeme)
+    }
+
+    fn visit_grouping(&mut self, expr: &Expr) -> String {
+        self.parenthesize("group", &[expr])
+    }
+
+    fn visit_literal(&mut self, value: &Object, _: Span) -> String {
+        value.to_string()
+    }
+
+    fn visit_logical(&mut self, lhs: &Expr, op: &Token, rhs: &Expr) -> String {
+        self.parenthesize(op.lexeme.as_str(), &[lhs, rhs])
+    }
+
+    fn visit_set(&mut self, object: &Expr, name: &Token, value: &Expr) -> String {
+        format!(
+            "(= (. {} {}) {})",
+            walk_expr(self, object),
+            name.lexeme,
+            walk_expr(self, value)
+        )
+    }
+
+    fn visit_stringify(&mut self, expr: &Expr) -> String {
+        self.parenthesize("str", &[expr])
+    }
+
+    fn visit_super(&mut self, method: &Token, _: &Depth) -> String {
+        format!("(super {})", method.lexeme)
+    }
+
+    fn visit_this(&mut self, _: &Token, _: &Depth) -> String {
+        "this".to_string()
+    }
+
+    fn visit_unary(&mut self, op: &Token, rhs: &Expr) -> String {
+        self.parenthesize(op.lexeme.as_str(), &[rhs])
+    }
+
+    fn visit_variable(&mut self, name: &Token, _: &Depth) -> String {
+        name.lexeme.to_string()
     }
 }
 
-fn eval_grouping(expr: Expr) -> Result<Object, Expr> {
-    eval(expr)
+/// How many Lox calls may be active at once before the interpreter reports a
+/// stack overflow instead of exhausting the native stack.
+pub const MAX_CALL_DEPTH: usize = 1000;
+
+/// Evaluates the tree directly, in the environment `env`.
+pub struct Interpreter {
+    env: Rc<RefCell<Environment>>,
+    /// Lox calls active around this one.
+    depth: usize,
 }
 
-fn eval_binary(lhs: Expr, op: Token, rhs: Expr) -> Result<Object, Expr> {
-    let lhs_res = eval(lhs.clone())?;
-    let rhs_res = eval(rhs.clone())?;
+impl Interpreter {
+    pub fn new(env: &Rc<RefCell<Environment>>) -> Interpreter {
+        Interpreter {
+            env: Rc::clone(env),
+            depth: 0,
+        }
+    }
 
-    if TokenType::BangEqual == op.token_type {
-        return Ok(Object::Boolean(lhs_res != rhs_res));
-    } else if TokenType::EqualEqual == op.token_type {
-        return Ok(Object::Boolean(lhs_res == rhs_res));
+    fn evaluate(&mut self, expr: &Expr) -> Result<Object, RuntimeError> {
+        walk_expr(self, expr)
     }
 
-    if let (Object::Number(lhs_res), Object::Number(rhs_res)) = (lhs_res.clone(), rhs_res.clone()) {
-        return match op.token_type {
-            TokenType::Minus => Ok(Object::Number(lhs_res - rhs_res)),
-            TokenType::Slash => Ok(Object::Number(lhs_res / rhs_res)),
-            TokenType::Star => Ok(Object::Number(lhs_res * rhs_res)),
-            TokenType::Plus => Ok(Object::Number(lhs_res + rhs_res)),
-            TokenType::Greater => Ok(Object::Boolean(lhs_res > rhs_res)),
-            TokenType::GreaterEqual => Ok(Object::Boolean(lhs_res >= rhs_res)),
-            TokenType::EqualEqual => Ok(Object::Boolean(lhs_res == rhs_res)),
-            TokenType::LessEqual => Ok(Object::Boolean(lhs_res <= rhs_res)),
-            TokenType::Less => Ok(Object::Boolean(lhs_res <= rhs_res)),
-            _ => Err(Expr::Binary(Box::new(lhs), op, Box::new(rhs))),
+    fn execute(&mut self, stmt: &Stmt) -> Result<Option<Object>, RuntimeError> {
+        walk_stmt(self, stmt)
+    }
+}
+
+/// Executes a statement. `Ok(Some(value))` means a `return` is unwinding
+/// towards the enclosing function call.
+pub fn eval_stmt(
+    stmt: &Stmt,
+    env: &Rc<RefCell<Environment>>,
+) -> Result<Option<Object>, RuntimeError> {
+    Interpreter::new(env).execute(stmt)
+}
+
+/// Executes `stmts` in `env`, `depth` calls deep.
+pub fn eval_block(
+    stmts: &[Stmt],
+    env: Rc<RefCell<Environment>>,
+    depth: usize,
+) -> Result<Option<Object>, RuntimeError> {
+    let mut interpreter = Interpreter { env, depth };
+    for stmt in stmts {
+        if let Some(value) = interpreter.execute(stmt)? {
+            return Ok(Some(value));
+        }
+    }
+    Ok(None)
+}
+
+impl ExprVisitor<Result<Object, RuntimeError>> for Interpreter {
+    fn visit_assign(
+        &mut self,
+        name: &Token,
+        value: &Expr,
+        depth: &Depth,
+    ) -> Result<Object, RuntimeError> {
+        let res = self.evaluate(value)?;
+        let assigned = match depth.get() {
+            Some(distance) => {
+                self.env
+                    .borrow_mut()
+                    .assign_at(distance, name.lexeme.as_str(), res.clone())
+            }
+            None => self
+                .env
+                .borrow_mut()
+                .assign_global(name.lexeme.as_str(), res.clone()),
         };
-    } else if let (Object::String(lhs_res), Object::String(rhs_res)) = (lhs_res, rhs_res) {
-        return match op.token_type {
-            TokenType::Plus => Ok(Object::String(format!("{}{}", lhs_res, rhs_res))),
-            _ => Err(Expr::Binary(Box::new(lhs), op, Box::new(rhs))),
+        if assigned {
+            Ok(res)
+        } else {
+            let message = format!("Undefined variable '{}'.", name.lexeme);
+            Err(RuntimeError::new(name.span, message))
+        }
+    }
+
+    fn visit_binary(&mut self, lhs: &Expr, op: &Token, rhs: &Expr) -> Result<Object, RuntimeError> {
+        let lhs_res = self.evaluate(lhs)?;
+        let rhs_res = self.evaluate(rhs)?;
+
+        if TokenType::BangEqual == op.token_type {
+            return Ok(Object::Boolean(!is_equal(&lhs_res, &rhs_res)));
+        } else if TokenType::EqualEqual == op.token_type {
+            return Ok(Object::Boolean(is_equal(&lhs_res, &rhs_res)));
+        }
+
+        let arithmetic = match (&lhs_res, &rhs_res) {
+            (Object::Integer(a), Object::Integer(b)) => {
+                Some(integer_binary(*a, &op.token_type, *b))
+            }
+            (a, b) => match (as_float(a), as_float(b)) {
+                (Some(a), Some(b)) => Some(float_binary(a, &op.token_type, b)),
+                _ => None,
+            },
+        };
+        if let Some(result) = arithmetic {
+            return result.map_err(|message| {
+                RuntimeError::new(lhs.span().to(rhs.span()), message.to_string())
+            });
+        }
+
+        match (lhs_res, rhs_res) {
+            (Object::String(lhs_res), Object::String(rhs_res))
+                if op.token_type == TokenType::Plus =>
+            {
+                Ok(Object::String(format!("{}{}", lhs_res, rhs_res)))
+            }
+            // Point at the operand that doesn't fit, not the whole expression.
+            (lhs_res, _) if op.token_type == TokenType::Plus => {
+                let span = match lhs_res {
+                    Object::Integer(_) | Object::Number(_) | Object::String(_) => rhs.span(),
+                    _ => lhs.span(),
+                };
+                Err(RuntimeError::new(
+                    span,
+                    "Operands must be two numbers or two strings.".to_string(),
+                ))
+            }
+            (lhs_res, _) => {
+                let span = match as_float(&lhs_res) {
+                    Some(_) => rhs.span(),
+                    None => lhs.span(),
+                };
+                Err(RuntimeError::new(
+                    span,
+                    "Operands must be numbers.".to_string(),
+                ))
+            }
+        }
+    }
+
+    fn visit_call(
+        &mut self,
+        callee: &Expr,
+        paren: &Token,
+        arguments: &[Expr],
+    ) -> Result<Object, RuntimeError> {
+        let callee_res = self.evaluate(callee)?;
+        let mut args = Vec::with_capacity(arguments.len());
+        for argument in arguments {
+            args.push(self.evaluate(argument)?);
+        }
+        let arity = match &callee_res {
+            Object::Function(function) => function.arity(),
+            Object::Class(class) => class.arity(),
+            _ => {
+                return Err(RuntimeError::new(
+                    callee.span(),
+                    "Can only call functions and classes.".to_string(),
+                ))
+            }
         };
-    } else {
-        return Err(Expr::Binary(Box::new(lhs), op, Box::new(rhs)));
+        if arity != args.len() {
+            let message = format!("Expected {} arguments but got {}.", arity, args.len());
+            return Err(RuntimeError::new(paren.span, message));
+        }
+        if self.depth >= MAX_CALL_DEPTH {
+            return Err(RuntimeError::new(paren.span, "Stack overflow.".to_string()));
+        }
+        // The frame is only named on the error path, to keep calls cheap.
+        let line = paren.span.line;
+        match callee_res {
+            Object::Function(function) => function
+                .call(args, self.depth + 1)
+                .map_err(|error| error.with_frame(format!("{:?}", function), line)),
+            Object::Class(class) => class
+                .call(args, self.depth + 1)
+                .map_err(|error| error.with_frame(format!("{:?}", class), line)),
+            _ => unreachable!("callee was checked to be callable"),
+        }
+    }
+
+    fn visit_get(&mut self, object: &Expr, name: &Token) -> Result<Object, RuntimeError> {
+        let object_res = self.evaluate(object)?;
+        let Object::Instance(instance) = &object_res else {
+            return Err(RuntimeError::new(
+                name.span,
+                "Only instances have properties.".to_string(),
+            ));
+        };
+        let value = instance.borrow().get(name.lexeme.as_str(), &object_res);
+        value.ok_or_else(|| {
+            let message = format!("Undefined property '{}'.", name.lexeme);
+            RuntimeError::new(name.span, message)
+        })
+    }
+
+    fn visit_grouping(&mut self, expr: &Expr) -> Result<Object, RuntimeError> {
+        self.evaluate(expr)
+    }
+
+    fn visit_literal(&mut self, value: &Object, _: Span) -> Result<Object, RuntimeError> {
+        Ok(value.clone())
+    }
+
+    fn visit_logical(
+        &mut self,
+        lhs: &Expr,
+        op: &Token,
+        rhs: &Expr,
+    ) -> Result<Object, RuntimeError> {
+        let lhs_res = self.evaluate(lhs)?;
+        let lhs_truthy = is_truthy(lhs_res.clone());
+        match op.token_type {
+            TokenType::Or if lhs_truthy => Ok(lhs_res),
+            TokenType::And if !lhs_truthy => Ok(lhs_res),
+            _ => self.evaluate(rhs),
+        }
+    }
+
+    fn visit_set(
+        &mut self,
+        object: &Expr,
+        name: &Token,
+        value: &Expr,
+    ) -> Result<Object, RuntimeError> {
+        let Object::Instance(instance) = self.evaluate(object)? else {
+            return Err(RuntimeError::new(
+                name.span,
+                "Only instances have fields.".to_string(),
+            ));
+        };
+        let value_res = self.evaluate(value)?;
+        instance
+            .borrow_mut()
+            .set(name.lexeme.to_string(), value_res.clone());
+        Ok(value_res)
+    }
+
+    fn visit_stringify(&mut self, expr: &Expr) -> Result<Object, RuntimeError> {
+        Ok(Object::String(self.evaluate(expr)?.to_string()))
+    }
+
+    fn visit_super(&mut self, method: &Token, depth: &Depth) -> Result<Object, RuntimeError> {
+        if let Some(distance) = depth.get() {
+            // `this` is always bound in the scope just inside the one holding `super`.
+            let superclass = self.env.borrow().get_at(distance, "super");
+            let this = self.env.borrow().get_at(distance - 1, "this");
+            if let (Some(Object::Class(superclass)), Some(this)) = (superclass, this) {
+                if let Some(function) = superclass.find_method(method.lexeme.as_str()) {
+                    return Ok(Object::Function(Rc::new(function.bind(this))));
+                }
+            }
+        }
+        let message = format!("Undefined property '{}'.", method.lexeme);
+        Err(RuntimeError::new(method.span, message))
+    }
+
+    fn visit_this(&mut self, keyword: &Token, depth: &Depth) -> Result<Object, RuntimeError> {
+        self.visit_variable(keyword, depth)
+    }
+
+    fn visit_unary(&mut self, op: &Token, rhs: &Expr) -> Result<Object, RuntimeError> {
+        let res = self.evaluate(rhs)?;
+        match (&op.token_type, res) {
+            (TokenType::Minus, Object::Number(n)) => Ok(Object::Number(-n)),
+            (TokenType::Minus, Object::Integer(n)) => {
+                n.checked_neg()
+                    .map(Object::Integer)
+                    .ok_or(RuntimeError::new(
+                        op.span.to(rhs.span()),
+                        "Integer overflow.".to_string(),
+                    ))
+            }
+            (TokenType::Minus, _) => Err(RuntimeError::new(
+                rhs.span(),
+                "Operand must be a number.".to_string(),
+            )),
+            (TokenType::Bang, res) => Ok(Object::Boolean(!is_truthy(res))),
+            (op, _) => unreachable!("not a unary operator: {:?}", op),
+        }
+    }
+
+    fn visit_variable(&mut self, name: &Token, depth: &Depth) -> Result<Object, RuntimeError> {
+        let value = match depth.get() {
+            Some(distance) => self.env.borrow().get_at(distance, name.lexeme.as_str()),
+            None => self.env.borrow().get_global(name.lexeme.as_str()),
+        };
+        value.ok_or_else(|| {
+            let message = format!("Undefined variable '{}'.", name.lexeme);
+            RuntimeError::new(name.span, message)
+        })
     }
 }
 
-fn eval_unary(token: Token, expr: Expr) -> Result<Object, Expr> {
-    let res = eval(expr.clone())?;
-    match (&token.token_type, &res) {
-        (TokenType::Minus, Object::Number(_)) => eval_negative(res, expr),
-        (TokenType::Bang, Object::Number(_)) => eval_bool(res),
-        _ => Err(expr),
+impl StmtVisitor<Result<Option<Object>, RuntimeError>> for Interpreter {
+    fn visit_block(&mut self, stmts: &[Stmt]) -> Result<Option<Object>, RuntimeError> {
+        eval_block(stmts, Environment::new_enclosing(&self.env), self.depth)
+    }
+
+    fn visit_class(
+        &mut self,
+        name: &Token,
+        superclass: Option<&Expr>,
+        methods: &[Rc<FunctionDecl>],
+    ) -> Result<Option<Object>, RuntimeError> {
+        let superclass = match superclass {
+            Some(expr) => match self.evaluate(expr)? {
+                Object::Class(class) => Some(class),
+                _ => {
+                    let message = "Superclass must be a class.".to_string();
+                    return Err(RuntimeError::new(expr.span(), message));
+                }
+            },
+            None => None,
+        };
+
+        let mut method_env = Rc::clone(&self.env);
+        if let Some(superclass) = &superclass {
+            method_env = Environment::new_enclosing(&self.env);
+            method_env
+                .borrow_mut()
+                .define("super".to_string(), Object::Class(Rc::clone(superclass)));
+        }
+
+        let methods = methods
+            .iter()
+            .map(|method| {
+                let is_initializer = method.name.lexeme.as_str() == "init";
+                let function =
+                    LoxFunction::new(Rc::clone(method), Rc::clone(&method_env), is_initializer);
+                (method.name.lexeme.to_string(), Rc::new(function))
+            })
+            .collect();
+        let class = LoxClass::new(name.lexeme.to_string(), superclass, methods);
+        self.env
+            .borrow_mut()
+            .define(name.lexeme.to_string(), Object::Class(Rc::new(class)));
+        Ok(None)
+    }
+
+    fn visit_expression(&mut self, expr: &Expr) -> Result<Option<Object>, RuntimeError> {
+        self.evaluate(expr)?;
+        Ok(None)
+    }
+
+    fn visit_function(
+        &mut self,
+        declaration: &Rc<FunctionDecl>,
+    ) -> Result<Option<Object>, RuntimeError> {
+        let function = LoxFunction::new(Rc::clone(declaration), Rc::clone(&self.env), false);
+        self.env.borrow_mut().define(
+            declaration.name.lexeme.to_string(),
+            Object::Function(Rc::new(function)),
+        );
+        Ok(None)
+    }
+
+    fn visit_if(
+        &mut self,
+        condition: &Expr,
+        then_branch: &Stmt,
+        else_branch: Option<&Stmt>,
+    ) -> Result<Option<Object>, RuntimeError> {
+        if is_truthy(self.evaluate(condition)?) {
+            self.execute(then_branch)
+        } else if let Some(else_branch) = else_branch {
+            self.execute(else_branch)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn visit_print(&mut self, expr: &Expr) -> Result<Option<Object>, RuntimeError> {
+        println!("{}", self.evaluate(expr)?);
+        Ok(None)
+    }
+
+    fn visit_return(
+        &mut self,
+        _: &Token,
+        value: Option<&Expr>,
+    ) -> Result<Option<Object>, RuntimeError> {
+        let value = match value {
+            Some(expr) => self.evaluate(expr)?,
+            None => Object::Nil,
+        };
+        Ok(Some(value))
+    }
+
+    fn visit_var(
+        &mut self,
+        name: &Token,
+        initializer: Option<&Expr>,
+    ) -> Result<Option<Object>, RuntimeError> {
+        let value = match initializer {
+            Some(expr) => self.evaluate(expr)?,
+            None => Object::Nil,
+        };
+        self.env.borrow_mut().define(name.lexeme.to_string(), value);
+        Ok(None)
+    }
+
+    fn visit_while(
+        &mut self,
+        condition: &Expr,
+        body: &Stmt,
+    ) -> Result<Option<Object>, RuntimeError> {
+        while is_truthy(self.evaluate(condition)?) {
+            if let Some(value) = self.execute(body)? {
+                return Ok(Some(value));
+            }
+        }
+        Ok(None)
     }
 }
 
-fn eval_negative(obj: Object, expr: Expr) -> Result<Object, Expr> {
-    if let Object::Number(n) = obj {
-        Ok(Object::Number(-1.0 * n))
-    } else {
-        Err(expr)
+/// Exact 64-bit arithmetic. Overflow is an error; division that isn't exact
+/// gives a float.
+fn integer_binary(a: i64, op: &TokenType, b: i64) -> Result<Object, &'static str> {
+    let value = match op {
+        TokenType::Minus => a.checked_sub(b),
+        TokenType::Plus => a.checked_add(b),
+        TokenType::Star => a.checked_mul(b),
+        TokenType::Slash => match a.checked_rem(b) {
+            Some(0) => a.checked_div(b),
+            Some(_) => return Ok(Object::Number(a as f64 / b as f64)),
+            None if b == 0 => return Ok(Object::Number(a as f64 / b as f64)),
+            None => None,
+        },
+        TokenType::Greater => return Ok(Object::Boolean(a > b)),
+        TokenType::GreaterEqual => return Ok(Object::Boolean(a >= b)),
+        TokenType::LessEqual => return Ok(Object::Boolean(a <= b)),
+        TokenType::Less => return Ok(Object::Boolean(a < b)),
+        _ => return Err("Unknown operator."),
+    };
+    value.map(Object::Integer).ok_or("Integer overflow.")
+}
+
+fn float_binary(a: f64, op: &TokenType, b: f64) -> Result<Object, &'static str> {
+    match op {
+        TokenType::Minus => Ok(Object::Number(a - b)),
+        TokenType::Slash => Ok(Object::Number(a / b)),
+        TokenType::Star => Ok(Object::Number(a * b)),
+        TokenType::Plus => Ok(Object::Number(a + b)),
+        TokenType::Greater => Ok(Object::Boolean(a > b)),
+        TokenType::GreaterEqual => Ok(Object::Boolean(a >= b)),
+        TokenType::LessEqual => Ok(Object::Boolean(a <= b)),
+        TokenType::Less => Ok(Object::Boolean(a < b)),
+        _ => Err("Unknown operator."),
     }
 }
 
-fn eval_bool(obj: Object) -> Result<Object, Expr> {
-    Ok(Object::Boolean(!is_truthy(obj)))
+/// Numbers of either kind, promoting integers to floats.
+fn as_float(obj: &Object) -> Option<f64> {
+    match obj {
+        Object::Integer(n) => Some(*n as f64),
+        Object::Number(n) => Some(*n),
+        _ => None,
+    }
+}
+
+/// `==` compares integers and floats by value.
+fn is_equal(lhs: &Object, rhs: &Object) -> bool {
+    match (lhs, rhs) {
+        (Object::Integer(a), Object::Number(b)) | (Object::Number(b), Object::Integer(a)) => {
+            *a as f64 == *b
+        }
+        _ => lhs == rhs,
+    }
 }
 
 fn is_truthy(obj: Object) -> bool {
@@ -121,19 +655,486 @@ fn is_truthy(obj: Object) -> bool {
     }
 }
 
-#[derive(Debug, Clone)]
-pub enum Stmt {
-    Expression(Box<Expr>),
-    Print(Box<Expr>),
-}
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::lox::Lox;
+    use crate::parser::{Parse, Parser};
+    use crate::resolver::{Resolve, Resolver};
+    use crate::scanner::Scanner;
 
-pub fn eval_stmt(stmt: &Stmt) -> Result<Object, Expr> {
-    let expr: &Expr = match stmt {
-        Stmt::Print(e) | Stmt::Expression(e) => e.as_ref(),
-    };
-    let result = eval(expr.clone())?;
-    if let Stmt::Print(_) = &stmt {
-        println!("{}", result);
+    fn run(code: &str, env: &Rc<RefCell<Environment>>) -> Result<(), RuntimeError> {
+        let stmts = Parser::new(Scanner::new(code.to_string(), Lox::new()), Lox::new()).parse();
+        let mut resolver = Resolver::new(Lox::new());
+        resolver.resolve(&stmts);
+        assert!(
+            !resolver.get_errors().had_error,
+            "resolve error in {}",
+            code
+        );
+        for stmt in stmts {
+            eval_stmt(&stmt, env)?;
+        }
+        Ok(())
+    }
+
+    fn run_err(code: &str, env: &Rc<RefCell<Environment>>) -> RuntimeError {
+        run(code, env).expect_err("expected a runtime error")
+    }
+
+    #[test]
+    fn test_var_declaration_and_assignment() {
+        let env = Environment::new();
+        run("var x = 1; var y; x = x + 1;", &env).unwrap();
+        assert_eq!(env.borrow().get_global("x"), Some(Object::Integer(2)));
+        assert_eq!(env.borrow().get_global("y"), Some(Object::Nil));
+    }
+
+    #[test]
+    fn test_environment_persists_across_runs() {
+        let env = Environment::new();
+        run("var greeting = \"hi\";", &env).unwrap();
+        run("greeting = greeting + \" there\";", &env).unwrap();
+        assert_eq!(
+            env.borrow().get_global("greeting"),
+            Some(Object::String("hi there".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_undefined_variable_carries_line() {
+        let env = Environment::new();
+        let error = run_err("var a = 1;\nprint b;", &env);
+        assert_eq!(error.span.line, 2);
+        assert_eq!(error.message, "Undefined variable 'b'.");
+        let error = run_err("\n\nc = 3;", &env);
+        assert_eq!(error.span.line, 3);
+        assert_eq!(error.message, "Undefined variable 'c'.");
+    }
+
+    #[test]
+    fn test_block_shadows_and_drops_inner_vars() {
+        let env = Environment::new();
+        run(
+            "var a = \"outer\"; var b = 1; { var a = \"inner\"; var c = 2; b = a; }",
+            &env,
+        )
+        .unwrap();
+        assert_eq!(
+            env.borrow().get_global("a"),
+            Some(Object::String("outer".to_string()))
+        );
+        assert_eq!(
+            env.borrow().get_global("b"),
+            Some(Object::String("inner".to_string()))
+        );
+        assert_eq!(env.borrow().get_global("c"), None);
+    }
+
+    #[test]
+    fn test_block_restores_outer_scope_on_error() {
+        let env = Environment::new();
+        assert!(run("var a = 1; { var a = 2; { var d = 4; missing; } }", &env).is_err());
+        assert_eq!(env.borrow().get_global("a"), Some(Object::Integer(1)));
+        assert_eq!(env.borrow().get_global("d"), None);
+        run("a = a + 1;", &env).unwrap();
+        assert_eq!(env.borrow().get_global("a"), Some(Object::Integer(2)));
+    }
+
+    #[test]
+    fn test_if_else() {
+        let env = Environment::new();
+        run(
+            "var a; var b; if (1 < 2) a = \"then\"; else a = \"else\"; if (nil) b = 1; else b = 2;",
+            &env,
+        )
+        .unwrap();
+        assert_eq!(
+            env.borrow().get_global("a"),
+            Some(Object::String("then".to_string()))
+        );
+        assert_eq!(env.borrow().get_global("b"), Some(Object::Integer(2)));
+    }
+
+    #[test]
+    fn test_dangling_else_binds_to_nearest_if() {
+        let env = Environment::new();
+        run(
+            "var a = \"unset\"; if (true) if (false) a = \"inner\"; else a = \"else\";",
+            &env,
+        )
+        .unwrap();
+        assert_eq!(
+            env.borrow().get_global("a"),
+            Some(Object::String("else".to_string()))
+        );
+        run(
+            "a = \"unset\"; if (false) if (true) a = 1; else a = 2;",
+            &env,
+        )
+        .unwrap();
+        assert_eq!(
+            env.borrow().get_global("a"),
+            Some(Object::String("unset".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_while_loop() {
+        let env = Environment::new();
+        run(
+            "var i = 0; var sum = 0; while (i < 5) { sum = sum + i; i = i + 1; }",
+            &env,
+        )
+        .unwrap();
+        assert_eq!(env.borrow().get_global("sum"), Some(Object::Integer(10)));
+    }
+
+    #[test]
+    fn test_nested_for_loops() {
+        let env = Environment::new();
+        run(
+            "var count = 0; for (var i = 0; i < 3; i = i + 1) { for (var j = 0; j < 4; j = j + 1) count = count + 1; }",
+            &env,
+        )
+        .unwrap();
+        assert_eq!(env.borrow().get_global("count"), Some(Object::Integer(12)));
+        assert_eq!(env.borrow().get_global("i"), None);
+    }
+
+    #[test]
+    fn test_for_loop_with_empty_clauses() {
+        let env = Environment::new();
+        run("var i = 0; for (; i < 3;) i = i + 1;", &env).unwrap();
+        assert_eq!(env.borrow().get_global("i"), Some(Object::Integer(3)));
+    }
+
+    #[test]
+    fn test_logical_returns_deciding_operand() {
+        let env = Environment::new();
+        run(
+            "var a = nil or \"yes\"; var b = 0 and false; var c = false and 1; var d = 1 or 2;",
+            &env,
+        )
+        .unwrap();
+        assert_eq!(
+            env.borrow().get_global("a"),
+            Some(Object::String("yes".to_string()))
+        );
+        assert_eq!(env.borrow().get_global("b"), Some(Object::Boolean(false)));
+        assert_eq!(env.borrow().get_global("c"), Some(Object::Boolean(false)));
+        assert_eq!(env.borrow().get_global("d"), Some(Object::Integer(1)));
+    }
+
+    #[test]
+    fn test_integer_arithmetic() {
+        let env = Environment::new();
+        run(
+            "var id = 9007199254740993 + 2; var half = 7 / 2; var exact = 8 / 2;
+             var mixed = 1 + 0.5; var same = 1 == 1.0; var s = \"${3} ${3.0} ${-0x10}\";",
+            &env,
+        )
+        .unwrap();
+        let get = |name| env.borrow().get_global(name);
+        assert_eq!(get("id"), Some(Object::Integer(9007199254740995)));
+        assert_eq!(get("half"), Some(Object::Number(3.5)));
+        assert_eq!(get("exact"), Some(Object::Integer(4)));
+        assert_eq!(get("mixed"), Some(Object::Number(1.5)));
+        assert_eq!(get("same"), Some(Object::Boolean(true)));
+        assert_eq!(get("s"), Some(Object::String("3 3 -16".to_string())));
+
+        for code in [
+            "0x7FFFFFFFFFFFFFFF + 1;",
+            "-(-0x7FFFFFFFFFFFFFFF - 1);",
+            "(-0x7FFFFFFFFFFFFFFF - 1) / -1;",
+        ] {
+            assert_eq!(
+                run_err(code, &env).message,
+                "Integer overflow.",
+                "for {}",
+                code
+            );
+        }
+    }
+
+    #[test]
+    fn test_string_interpolation() {
+        let env = Environment::new();
+        run(
+            "class P {} var a = 1; var b = 2.5;
+             var s = \"sum ${a + b}, ${nil} ${P} ${\"in ${a > 0}\"}\";",
+            &env,
+        )
+        .unwrap();
+        assert_eq!(
+            env.borrow().get_global("s"),
+            Some(Object::String("sum 3.5, nil P in true".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_logical_short_circuits() {
+        let env = Environment::new();
+        assert!(run("true or undefined;", &env).is_ok());
+        assert!(run("nil and undefined;", &env).is_ok());
+        assert!(run("false or undefined;", &env).is_err());
+        assert!(run("1 and undefined;", &env).is_err());
+    }
+
+    #[test]
+    fn test_function_call_and_return() {
+        let env = Environment::new();
+        run(
+            "fn add(a, b) { return a + b; } fn noop() {} var sum = add(1, 2); var nothing = noop();",
+            &env,
+        )
+        .unwrap();
+        assert_eq!(env.borrow().get_global("sum"), Some(Object::Integer(3)));
+        assert_eq!(env.borrow().get_global("nothing"), Some(Object::Nil));
+        assert_eq!(
+            format!("{}", env.borrow().get_global("add").unwrap()),
+            "<fn add>"
+        );
+    }
+
+    #[test]
+    fn test_return_unwinds_nested_blocks_and_loops() {
+        let env = Environment::new();
+        run(
+            "fn find(n) { for (var i = 0; i < 10; i = i + 1) { while (true) { if (i == n) { return i; } i = i + 1; } } return -1; } var found = find(4);",
+            &env,
+        )
+        .unwrap();
+        assert_eq!(env.borrow().get_global("found"), Some(Object::Integer(4)));
+    }
+
+    #[test]
+    fn test_closures_capture_defining_environment() {
+        let env = Environment::new();
+        run(
+            "fn makeCounter() { var i = 0; fn count() { i = i + 1; return i; } return count; } var counter = makeCounter(); counter(); var second = counter();",
+            &env,
+        )
+        .unwrap();
+        assert_eq!(env.borrow().get_global("second"), Some(Object::Integer(2)));
+        assert_eq!(env.borrow().get_global("i"), None);
+    }
+
+    #[test]
+    fn test_call_arity_and_callee_errors() {
+        let env = Environment::new();
+        run("fn one(a) { return a; }", &env).unwrap();
+        assert_eq!(
+            run_err("one();", &env).message,
+            "Expected 1 arguments but got 0."
+        );
+        assert_eq!(
+            run_err("one(1, 2);", &env).message,
+            "Expected 1 arguments but got 2."
+        );
+        assert_eq!(
+            run_err("\"text\"();", &env).message,
+            "Can only call functions and classes."
+        );
+    }
+
+    #[test]
+    fn test_class_fields_methods_and_this() {
+        let env = Environment::new();
+        run(
+            "class Point { init(x, y) { this.x = x; this.y = y; } sum() { return this.x + this.y; } }
+             var p = Point(1, 2); p.y = 10; var total = p.sum(); var desc = p;",
+            &env,
+        )
+        .unwrap();
+        assert_eq!(env.borrow().get_global("total"), Some(Object::Integer(11)));
+        assert_eq!(
+            format!("{}", env.borrow().get_global("Point").unwrap()),
+            "Point"
+        );
+        assert_eq!(
+            format!("{}", env.borrow().get_global("desc").unwrap()),
+            "Point instance"
+        );
+    }
+
+    #[test]
+    fn test_instances_have_reference_semantics() {
+        let env = Environment::new();
+        run(
+            "class Box {} var a = Box(); var b = a; b.value = 5; var seen = a.value; var same = a == b; var other = a == Box();",
+            &env,
+        )
+        .unwrap();
+        assert_eq!(env.borrow().get_global("seen"), Some(Object::Integer(5)));
+        assert_eq!(env.borrow().get_global("same"), Some(Object::Boolean(true)));
+        assert_eq!(
+            env.borrow().get_global("other"),
+            Some(Object::Boolean(false))
+        );
+    }
+
+    #[test]
+    fn test_bound_methods_keep_this() {
+        let env = Environment::new();
+        run(
+            "class Greeter { init(name) { this.name = name; return; } greet() { return \"hi \" + this.name; } }
+             var greet = Greeter(\"lox\").greet; var message = greet(); var again = Greeter(\"a\").init(\"b\").name;",
+            &env,
+        )
+        .unwrap();
+        assert_eq!(
+            env.borrow().get_global("message"),
+            Some(Object::String("hi lox".to_string()))
+        );
+        assert_eq!(
+            env.borrow().get_global("again"),
+            Some(Object::String("b".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_property_errors() {
+        let env = Environment::new();
+        run("class Empty {} var e = Empty();", &env).unwrap();
+        assert_eq!(
+            run_err("e.missing;", &env).message,
+            "Undefined property 'missing'."
+        );
+        assert_eq!(
+            run_err("var n = 1; n.field = 2;", &env).message,
+            "Only instances have fields."
+        );
+        assert_eq!(
+            run_err("n.field;", &env).message,
+            "Only instances have properties."
+        );
+        assert_eq!(
+            run_err("Empty(1);", &env).message,
+            "Expected 0 arguments but got 1."
+        );
+    }
+
+    #[test]
+    fn test_inherited_methods_and_super_calls() {
+        let env = Environment::new();
+        run(
+            "class A { init(n) { this.n = n; } name() { return \"A\"; } value() { return this.n; } }
+             class B < A { init(n) { super.init(n * 2); } name() { return \"B\" + super.name(); } }
+             class C < B { name() { return \"C\" + super.name(); } }
+             var c = C(2); var name = c.name(); var value = c.value();",
+            &env,
+        )
+        .unwrap();
+        assert_eq!(
+            env.borrow().get_global("name"),
+            Some(Object::String("CBA".to_string()))
+        );
+        assert_eq!(env.borrow().get_global("value"), Some(Object::Integer(4)));
+    }
+
+    #[test]
+    fn test_runtime_superclass_errors() {
+        let env = Environment::new();
+        assert_eq!(
+            run_err("var NotAClass = 1; class B < NotAClass {}", &env).message,
+            "Superclass must be a class."
+        );
+        assert_eq!(
+            run_err(
+                "class D {} class E < D { f() { return super.missing(); } } E().f();",
+                &env
+            )
+            .message,
+            "Undefined property 'missing'."
+        );
+    }
+
+    #[test]
+    fn test_closure_keeps_resolved_binding() {
+        let env = Environment::new();
+        run(
+            "var a = \"global\"; var first; var second;
+             { fn showA() { return a; } first = showA(); var a = \"block\"; second = showA(); }",
+            &env,
+        )
+        .unwrap();
+        assert_eq!(
+            env.borrow().get_global("first"),
+            Some(Object::String("global".to_string()))
+        );
+        assert_eq!(
+            env.borrow().get_global("second"),
+            Some(Object::String("global".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_operand_errors() {
+        let env = Environment::new();
+        let code = "var a = 1;\nvar b = a - \"x\";";
+        let error = run_err(code, &env);
+        assert_eq!(error.message, "Operands must be numbers.");
+        assert_eq!(&code[error.span.start..error.span.end], "\"x\"");
+        assert_eq!((error.span.line, error.span.column), (2, 13));
+        let code = "var c = nil + 1 + 2;";
+        let error = run_err(code, &env);
+        assert_eq!(
+            error.message,
+            "Operands must be two numbers or two strings."
+        );
+        assert_eq!(&code[error.span.start..error.span.end], "nil");
+        assert_eq!(
+            run_err("-\"x\";", &env).message,
+            "Operand must be a number."
+        );
+    }
+
+    #[test]
+    fn test_runtime_error_records_call_frames() {
+        let env = Environment::new();
+        let error = run_err(
+            "fn inner() { return 1 < nil; }\nfn outer() { return inner(); }\nouter();",
+            &env,
+        );
+        assert_eq!(error.span.line, 1);
+        let frames: Vec<(String, usize)> = error
+            .frames
+            .iter()
+            .map(|frame| (frame.function.clone(), frame.line))
+            .collect();
+        assert_eq!(
+            frames,
+            vec![("<fn inner>".to_string(), 2), ("<fn outer>".to_string(), 3)]
+        );
+    }
+
+    #[test]
+    fn test_deep_recursion_reports_stack_overflow() {
+        // The test harness's threads have too little stack for the limit.
+        let interpreter = std::thread::Builder::new()
+            .stack_size(crate::STACK_SIZE)
+            .spawn(|| {
+                let env = Environment::new();
+                let count = format!(
+                    "fn count(n) {{ if (n == 0) return 0; return count(n - 1); }} count({});",
+                    MAX_CALL_DEPTH - 1
+                );
+                run(&count, &env).unwrap();
+                let error = run_err(
+                    "fn f() { f(); }
+f();",
+                    &env,
+                );
+                error.to_string()
+            })
+            .unwrap();
+        assert_eq!(
+            interpreter.join().unwrap(),
+            format!(
+                "Stack overflow.\n[line 1]\n  in <fn f> called from [line 1]\n  ... repeated {} more times\n  in <fn f> called from [line 2]",
+                MAX_CALL_DEPTH - 2
+            )
+        );
     }
-    Ok(result)
 }
@@ -0,0 +1,100 @@
+use std::{cell::RefCell, collections::HashMap, fmt::Debug, rc::Rc};
+
+use crate::ast::Object;
+use crate::function::LoxFunction;
+use crate::lox::RuntimeError;
+
+pub struct LoxClass {
+    name: String,
+    superclass: Option<Rc<LoxClass>>,
+    methods: HashMap<String, Rc<LoxFunction>>,
+}
+
+impl LoxClass {
+    pub fn new(
+        name: String,
+        superclass: Option<Rc<LoxClass>>,
+        methods: HashMap<String, Rc<LoxFunction>>,
+    ) -> LoxClass {
+        LoxClass {
+            name,
+            superclass,
+            methods,
+        }
+    }
+
+    pub fn find_method(&self, name: &str) -> Option<Rc<LoxFunction>> {
+        match self.methods.get(name) {
+            Some(method) => Some(Rc::clone(method)),
+            None => self.superclass.as_ref()?.find_method(name),
+        }
+    }
+
+    pub fn arity(&self) -> usize {
+        self.find_method("init").map_or(0, |init| init.arity())
+    }
+
+    pub fn call(
+        self: &Rc<Self>,
+        arguments: Vec<Object>,
+        depth: usize,
+    ) -> Result<Object, RuntimeError> {
+        let instance = Object::Instance(Rc::new(RefCell::new(LoxInstance::new(Rc::clone(self)))));
+        if let Some(init) = self.find_method("init") {
+            init.bind(instance.clone()).call(arguments, depth)?;
+        }
+        Ok(instance)
+    }
+}
+
+impl PartialEq for LoxClass {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl Debug for LoxClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+pub struct LoxInstance {
+    class: Rc<LoxClass>,
+    fields: HashMap<String, Object>,
+}
+
+impl LoxInstance {
+    pub fn new(class: Rc<LoxClass>) -> LoxInstance {
+        LoxInstance {
+            class,
+            fields: HashMap::new(),
+        }
+    }
+
+    /// Looks up a field, falling back to a method bound to `this`. The
+    /// instance is passed in as an `Object` so the bound method shares it.
+    pub fn get(&self, name: &str, this: &Object) -> Option<Object> {
+        if let Some(value) = self.fields.get(name) {
+            return Some(value.clone());
+        }
+        let method = self.class.find_method(name)?;
+        Some(Object::Function(Rc::new(method.bind(this.clone()))))
+    }
+
+    pub fn set(&mut self, name: String, value: Object) {
+        self.fields.insert(name, value);
+    }
+}
+
+impl PartialEq for LoxInstance {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl Debug for LoxInstance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?} instance", self.class)
+    }
+}
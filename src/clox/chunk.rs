@@ -1,15 +1,61 @@
-use std::ptr::null_mut;
+use std::fmt::Display;
 
-use super::memory::{grow_capacity, reallocate};
+use super::memory::DynArray;
+use super::value::{Value, ValueArray};
 
 pub trait Chunkable {
     fn init() -> Self;
-    fn write(&mut self, byte: u8);
-    fn free(&mut self);
+    fn write(&mut self, byte: u8, line: usize);
 }
 
+/// The most constants a chunk can hold: `OpConstantLong` has a 24-bit operand.
+pub const MAX_CONSTANTS: usize = 1 << 24;
+
+/// The constant pool already holds `MAX_CONSTANTS` values.
+#[derive(Debug, PartialEq)]
+pub struct TooManyConstants;
+
+impl Display for TooManyConstants {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Too many constants in one chunk.")
+    }
+}
+
+#[derive(Debug, PartialEq)]
 pub enum OpCode {
     OpReturn,
+    /// Pushes the constant at a one-byte index.
+    OpConstant,
+    /// Pushes the constant at a three-byte little-endian index.
+    OpConstantLong,
+    OpNil,
+    OpTrue,
+    OpFalse,
+    OpEqual,
+    OpGreater,
+    OpLess,
+    OpAdd,
+    OpSubtract,
+    OpMultiply,
+    OpDivide,
+    OpNot,
+    OpNegate,
+    OpPrint,
+    OpPop,
+    /// Global operands are the three-byte constant index of the name.
+    OpDefineGlobal,
+    OpGetGlobal,
+    OpSetGlobal,
+    /// Local operands are a one-byte stack slot.
+    OpGetLocal,
+    OpSetLocal,
+    /// Jump operands are a two-byte little-endian distance forwards.
+    OpJump,
+    OpJumpIfFalse,
+    /// Jumps backwards by its two-byte operand.
+    OpLoop,
+    /// Replaces the top of the stack with its printed form.
+    OpStringify,
     Unknown,
 }
 
@@ -17,68 +63,164 @@ impl std::convert::From<u8> for OpCode {
     fn from(value: u8) -> Self {
         match value {
             0 => OpCode::OpReturn,
+            1 => OpCode::OpConstant,
+            2 => OpCode::OpConstantLong,
+            3 => OpCode::OpNil,
+            4 => OpCode::OpTrue,
+            5 => OpCode::OpFalse,
+            6 => OpCode::OpEqual,
+            7 => OpCode::OpGreater,
+            8 => OpCode::OpLess,
+            9 => OpCode::OpAdd,
+            10 => OpCode::OpSubtract,
+            11 => OpCode::OpMultiply,
+            12 => OpCode::OpDivide,
+            13 => OpCode::OpNot,
+            14 => OpCode::OpNegate,
+            15 => OpCode::OpPrint,
+            16 => OpCode::OpPop,
+            17 => OpCode::OpDefineGlobal,
+            18 => OpCode::OpGetGlobal,
+            19 => OpCode::OpSetGlobal,
+            20 => OpCode::OpGetLocal,
+            21 => OpCode::OpSetLocal,
+            22 => OpCode::OpJump,
+            23 => OpCode::OpJumpIfFalse,
+            24 => OpCode::OpLoop,
+            25 => OpCode::OpStringify,
             _ => OpCode::Unknown,
         }
     }
 }
 
-impl std::convert::Into<u8> for OpCode {
-    fn into(self) -> u8 {
-        match self {
+impl std::convert::From<OpCode> for u8 {
+    fn from(val: OpCode) -> Self {
+        match val {
             OpCode::OpReturn => 0,
+            OpCode::OpConstant => 1,
+            OpCode::OpConstantLong => 2,
+            OpCode::OpNil => 3,
+            OpCode::OpTrue => 4,
+            OpCode::OpFalse => 5,
+            OpCode::OpEqual => 6,
+            OpCode::OpGreater => 7,
+            OpCode::OpLess => 8,
+            OpCode::OpAdd => 9,
+            OpCode::OpSubtract => 10,
+            OpCode::OpMultiply => 11,
+            OpCode::OpDivide => 12,
+            OpCode::OpNot => 13,
+            OpCode::OpNegate => 14,
+            OpCode::OpPrint => 15,
+            OpCode::OpPop => 16,
+            OpCode::OpDefineGlobal => 17,
+            OpCode::OpGetGlobal => 18,
+            OpCode::OpSetGlobal => 19,
+            OpCode::OpGetLocal => 20,
+            OpCode::OpSetLocal => 21,
+            OpCode::OpJump => 22,
+            OpCode::OpJumpIfFalse => 23,
+            OpCode::OpLoop => 24,
+            OpCode::OpStringify => 25,
             OpCode::Unknown => 100,
         }
     }
 }
 
+/// The first byte of a run of bytes compiled from the same source line.
+#[derive(Clone, Copy)]
+pub struct LineStart {
+    pub offset: usize,
+    pub line: usize,
+}
+
 pub struct Chunk {
-    pub code: *mut u8,
-    pub count: usize,
-    pub capacity: usize,
+    pub code: DynArray<u8>,
+    pub constants: ValueArray,
+    /// Run-length encoded lines, one entry per change of line.
+    pub lines: DynArray<LineStart>,
 }
 
 impl Chunkable for Chunk {
     fn init() -> Chunk {
         Chunk {
-            code: null_mut(),
-            count: 0,
-            capacity: 0,
+            code: DynArray::new(),
+            constants: DynArray::new(),
+            lines: DynArray::new(),
         }
     }
 
-    fn write(&mut self, byte: u8) {
-        if self.is_at_capacity() {
-            self.resize();
+    fn write(&mut self, byte: u8, line: usize) {
+        if self.lines.last().map(|start| start.line) != Some(line) {
+            self.lines.push(LineStart {
+                offset: self.code.len(),
+                line,
+            });
         }
-        unsafe {
-            *self.code.add(self.count) = byte;
-        }
-        self.count += 1;
-    }
-
-    fn free(&mut self) {
-        *self = Chunk::init();
+        self.code.push(byte);
     }
 }
 
 impl Chunk {
-    fn is_at_capacity(&self) -> bool {
-        self.capacity < self.count + 1
+    /// Adds `value` to the constant pool and returns its index.
+    pub fn add_constant(&mut self, value: Value) -> Result<usize, TooManyConstants> {
+        if self.constants.len() == MAX_CONSTANTS {
+            return Err(TooManyConstants);
+        }
+        self.constants.push(value);
+        Ok(self.constants.len() - 1)
     }
 
-    fn resize(&mut self) {
-        let old_capacity = self.capacity;
-        self.capacity = grow_capacity(self.capacity);
-        self.code = reallocate(self.code, old_capacity, self.capacity);
+    /// Adds `value` to the constant pool and writes the instruction loading
+    /// it, using `OpConstantLong` once the index no longer fits in a byte.
+    pub fn write_constant(&mut self, value: Value, line: usize) -> Result<(), TooManyConstants> {
+        let index = self.add_constant(value)?;
+        if let Ok(index) = u8::try_from(index) {
+            self.write(OpCode::OpConstant.into(), line);
+            self.write(index, line);
+        } else {
+            self.write(OpCode::OpConstantLong.into(), line);
+            self.write_u24(index, line);
+        }
+        Ok(())
     }
-}
 
-impl Drop for Chunk {
-    fn drop(&mut self) {
-        if !self.code.is_null() {
-            reallocate(self.code, self.capacity, 0);
+    /// Writes the low three bytes of `value`, little-endian.
+    pub fn write_u24(&mut self, value: usize, line: usize) {
+        for byte in &value.to_le_bytes()[..3] {
+            self.write(*byte, line);
         }
     }
+
+    /// Overwrites the byte at `offset`, e.g. to fill in a jump distance.
+    pub fn patch(&mut self, offset: usize, byte: u8) {
+        self.code[offset] = byte;
+    }
+
+    /// The byte at `offset`.
+    pub fn read(&self, offset: usize) -> u8 {
+        self.code[offset]
+    }
+
+    /// The two-byte little-endian operand at `offset`.
+    pub fn read_u16(&self, offset: usize) -> u16 {
+        u16::from_le_bytes([self.read(offset), self.read(offset + 1)])
+    }
+
+    /// The three-byte little-endian operand at `offset`.
+    pub fn read_u24(&self, offset: usize) -> usize {
+        (0..3).fold(0, |value, i| {
+            value | (self.read(offset + i) as usize) << (8 * i)
+        })
+    }
+
+    /// The source line the byte at `offset` was compiled from.
+    pub fn get_line(&self, offset: usize) -> usize {
+        assert!(offset < self.code.len(), "offset {} out of bounds", offset);
+        let starts = self.lines.as_slice();
+        let run = starts.partition_point(|start| start.offset <= offset);
+        starts[run - 1].line
+    }
 }
 
 #[cfg(test)]
@@ -88,39 +230,71 @@ mod test {
     #[test]
     fn test_chunk_init() {
         let chunk = Chunk::init();
-        assert_eq!(chunk.code, null_mut());
+        assert_eq!(chunk.code.len(), 0);
+        assert_eq!(chunk.code.capacity(), 0);
     }
 
     #[test]
     fn test_chunk_write_value() {
         let chunk: Chunk = init_chunk();
-        unsafe {
-            assert_eq!(*chunk.code.add(0), 5);
-        }
+        assert_eq!(chunk.code[0], 5);
     }
 
     #[test]
     fn test_chunk_write_count() {
         let chunk = init_chunk();
-        assert_eq!(chunk.count, 1);
+        assert_eq!(chunk.code.len(), 1);
     }
 
     #[test]
     fn test_chunk_write_capacity() {
         let chunk = init_chunk();
-        assert_eq!(chunk.capacity, 8);
+        assert_eq!(chunk.code.capacity(), 8);
     }
 
     #[test]
-    fn test_chunk_free() {
-        let mut chunk = init_chunk();
-        chunk.free();
-        assert_eq!(chunk.code, null_mut());
+    fn test_add_constant_returns_index() {
+        let mut chunk = Chunk::init();
+        assert_eq!(chunk.add_constant(Value::Number(1.5)), Ok(0));
+        assert_eq!(chunk.add_constant(Value::Bool(true)), Ok(1));
+        assert_eq!(chunk.constants[1], Value::Bool(true));
+    }
+
+    #[test]
+    fn test_write_constant_switches_to_long_operand() {
+        let mut chunk = Chunk::init();
+        for i in 0..300 {
+            chunk.write_constant(Value::Number(i as f64), 1).unwrap();
+        }
+        assert_eq!(chunk.read(0), u8::from(OpCode::OpConstant));
+        assert_eq!(chunk.read(1), 0);
+        let long = 256 * 2;
+        assert_eq!(chunk.read(long), u8::from(OpCode::OpConstantLong));
+        assert_eq!(
+            [
+                chunk.read(long + 1),
+                chunk.read(long + 2),
+                chunk.read(long + 3)
+            ],
+            [0, 1, 0]
+        );
+        assert_eq!(chunk.code.len(), 256 * 2 + 44 * 4);
+    }
+
+    #[test]
+    fn test_lines_are_run_length_encoded() {
+        let mut chunk = Chunk::init();
+        for (byte, line) in [(0, 1), (1, 1), (2, 1), (3, 4), (4, 4), (5, 2)] {
+            chunk.write(byte, line);
+        }
+        assert_eq!(chunk.lines.len(), 3);
+        let lines: Vec<usize> = (0..chunk.code.len()).map(|i| chunk.get_line(i)).collect();
+        assert_eq!(lines, vec![1, 1, 1, 4, 4, 2]);
     }
 
     fn init_chunk() -> Chunk {
         let mut chunk = Chunk::init();
-        chunk.write(5);
-        return chunk;
+        chunk.write(5, 1);
+        chunk
     }
 }
@@ -0,0 +1,808 @@
+use std::collections::HashMap;
+use std::mem::discriminant;
+
+use super::chunk::{Chunk, Chunkable, OpCode};
+use super::value::Value;
+use crate::lox::{ErrorCode, Lox};
+use crate::parser::eof;
+use crate::token::{Span, Symbol, Token, TokenType};
+
+/// Locals are addressed by a one-byte stack slot.
+const MAX_LOCALS: usize = 256;
+
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+enum Precedence {
+    None,
+    Assignment,
+    Or,
+    And,
+    Equality,
+    Comparison,
+    Term,
+    Factor,
+    Unary,
+    Call,
+    Primary,
+}
+
+impl Precedence {
+    /// The next tighter level, used for the right operand of a
+    /// left-associative operator.
+    fn next(self) -> Precedence {
+        match self {
+            Precedence::None => Precedence::Assignment,
+            Precedence::Assignment => Precedence::Or,
+            Precedence::Or => Precedence::And,
+            Precedence::And => Precedence::Equality,
+            Precedence::Equality => Precedence::Comparison,
+            Precedence::Comparison => Precedence::Term,
+            Precedence::Term => Precedence::Factor,
+            Precedence::Factor => Precedence::Unary,
+            Precedence::Unary => Precedence::Call,
+            Precedence::Call | Precedence::Primary => Precedence::Primary,
+        }
+    }
+}
+
+/// Compiles the expression starting at the previous token. The flag says
+/// whether an `=` may follow, i.e. whether this is an assignment target.
+type ParseFn<I> = fn(&mut Compiler<I>, bool);
+
+struct ParseRule<I: Iterator<Item = Token>> {
+    prefix: Option<ParseFn<I>>,
+    infix: Option<ParseFn<I>>,
+    precedence: Precedence,
+}
+
+fn rule<I: Iterator<Item = Token>>(
+    prefix: Option<ParseFn<I>>,
+    infix: Option<ParseFn<I>>,
+    precedence: Precedence,
+) -> ParseRule<I> {
+    ParseRule {
+        prefix,
+        infix,
+        precedence,
+    }
+}
+
+struct Local {
+    name: Symbol,
+    /// `None` while the variable's own initializer is being compiled.
+    depth: Option<usize>,
+}
+
+/// A single-pass compiler: pulls tokens from `tokens` and emits bytecode
+/// as it parses, with no AST in between.
+pub struct Compiler<I: Iterator<Item = Token>> {
+    tokens: I,
+    current: Token,
+    previous: Token,
+    errors: Lox,
+    /// Set after a syntax error until the next statement boundary, so one
+    /// mistake isn't reported many times over.
+    panic_mode: bool,
+    chunk: Chunk,
+    locals: Vec<Local>,
+    scope_depth: usize,
+    /// Constant indices of the global names already in the pool.
+    names: HashMap<Symbol, usize>,
+}
+
+impl<I: Iterator<Item = Token>> Compiler<I> {
+    pub fn new(tokens: impl IntoIterator<Item = Token, IntoIter = I>, errors: Lox) -> Compiler<I> {
+        let mut compiler = Compiler {
+            tokens: tokens.into_iter(),
+            current: eof(Span::default()),
+            previous: eof(Span::default()),
+            errors,
+            panic_mode: false,
+            chunk: Chunk::init(),
+            locals: Vec::new(),
+            scope_depth: 0,
+            names: HashMap::new(),
+        };
+        compiler.advance();
+        compiler
+    }
+
+    /// Compiles the whole program. The chunk is only fit to run if
+    /// `get_errors` and the scanner's errors are both clean.
+    pub fn compile(&mut self) -> Chunk {
+        while !self.match_token(TokenType::EOF) {
+            self.declaration();
+        }
+        self.emit_op(OpCode::OpReturn);
+        std::mem::replace(&mut self.chunk, Chunk::init())
+    }
+
+    pub fn get_errors(&self) -> Lox {
+        self.errors.clone()
+    }
+
+    /// The token source, e.g. to collect the scanner's errors after compiling.
+    pub fn tokens(&self) -> &I {
+        &self.tokens
+    }
+
+    fn get_rule(token_type: TokenType) -> ParseRule<I> {
+        match token_type {
+            TokenType::LeftParen => rule(Some(Self::grouping), None, Precedence::None),
+            TokenType::Minus => rule(Some(Self::unary), Some(Self::binary), Precedence::Term),
+            TokenType::Plus => rule(None, Some(Self::binary), Precedence::Term),
+            TokenType::Slash | TokenType::Star => {
+                rule(None, Some(Self::binary), Precedence::Factor)
+            }
+            TokenType::Bang => rule(Some(Self::unary), None, Precedence::None),
+            TokenType::BangEqual | TokenType::EqualEqual => {
+                rule(None, Some(Self::binary), Precedence::Equality)
+            }
+            TokenType::Greater
+            | TokenType::GreaterEqual
+            | TokenType::Less
+            | TokenType::LessEqual => rule(None, Some(Self::binary), Precedence::Comparison),
+            TokenType::Identifier => rule(Some(Self::variable), None, Precedence::None),
+            TokenType::String => rule(Some(Self::string), None, Precedence::None),
+            TokenType::Interpolation => rule(Some(Self::interpolation), None, Precedence::None),
+            TokenType::Integer(_) | TokenType::Number(_) => {
+                rule(Some(Self::number), None, Precedence::None)
+            }
+            TokenType::And => rule(None, Some(Self::and), Precedence::And),
+            TokenType::Or => rule(None, Some(Self::or), Precedence::Or),
+            TokenType::False | TokenType::Nil | TokenType::True => {
+                rule(Some(Self::literal), None, Precedence::None)
+            }
+            _ => rule(None, None, Precedence::None),
+        }
+    }
+
+    fn declaration(&mut self) {
+        if self.match_token(TokenType::Var) {
+            self.var_declaration();
+        } else if self.match_token(TokenType::Fn) {
+            self.unsupported("Functions are not supported by the bytecode VM yet.");
+        } else if self.match_token(TokenType::Class) {
+            self.unsupported("Classes are not supported by the bytecode VM yet.");
+        } else {
+            self.statement();
+        }
+        if self.panic_mode {
+            self.synchronize();
+        }
+    }
+
+    /// Reports a declaration the VM can't run and skips to its closing brace.
+    fn unsupported(&mut self, message: &str) {
+        self.error(message);
+        let mut depth = 0;
+        loop {
+            match self.current.token_type {
+                TokenType::EOF => break,
+                TokenType::LeftBrace => depth += 1,
+                TokenType::RightBrace if depth <= 1 => {
+                    self.advance();
+                    break;
+                }
+                TokenType::RightBrace => depth -= 1,
+                _ => {}
+            }
+            self.advance();
+        }
+        self.panic_mode = false;
+    }
+
+    fn var_declaration(&mut self) {
+        let global = self.parse_variable("Expect variable name.");
+        if self.match_token(TokenType::Equal) {
+            self.expression();
+        } else {
+            self.emit_op(OpCode::OpNil);
+        }
+        self.consume(
+            TokenType::Semicolon,
+            "Expect ';' after variable declaration.",
+        );
+        self.define_variable(global);
+    }
+
+    fn statement(&mut self) {
+        if self.match_token(TokenType::Print) {
+            self.print_statement();
+        } else if self.match_token(TokenType::If) {
+            self.if_statement();
+        } else if self.match_token(TokenType::While) {
+            self.while_statement();
+        } else if self.match_token(TokenType::For) {
+            self.for_statement();
+        } else if self.match_token(TokenType::Return) {
+            self.error_at(
+                self.previous.clone(),
+                ErrorCode::Resolve,
+                "Can't return from top-level code.",
+            );
+        } else if self.match_token(TokenType::LeftBrace) {
+            self.begin_scope();
+            self.block();
+            self.end_scope();
+        } else {
+            self.expression_statement();
+        }
+    }
+
+    fn print_statement(&mut self) {
+        self.expression();
+        self.consume(TokenType::Semicolon, "Expect ';' after value.");
+        self.emit_op(OpCode::OpPrint);
+    }
+
+    fn expression_statement(&mut self) {
+        self.expression();
+        self.consume(TokenType::Semicolon, "Expect ';' after value.");
+        self.emit_op(OpCode::OpPop);
+    }
+
+    fn if_statement(&mut self) {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'if'.");
+        self.expression();
+        self.consume(TokenType::RightParen, "Expect ')' after if condition.");
+
+        let then_jump = self.emit_jump(OpCode::OpJumpIfFalse);
+        self.emit_op(OpCode::OpPop);
+        self.statement();
+        let else_jump = self.emit_jump(OpCode::OpJump);
+        self.patch_jump(then_jump);
+        self.emit_op(OpCode::OpPop);
+        if self.match_token(TokenType::Else) {
+            self.statement();
+        }
+        self.patch_jump(else_jump);
+    }
+
+    fn while_statement(&mut self) {
+        let loop_start = self.chunk.code.len();
+        self.consume(TokenType::LeftParen, "Expect '(' after 'while'.");
+        self.expression();
+        self.consume(TokenType::RightParen, "Expect ')' after condition.");
+
+        let exit_jump = self.emit_jump(OpCode::OpJumpIfFalse);
+        self.emit_op(OpCode::OpPop);
+        self.statement();
+        self.emit_loop(loop_start);
+        self.patch_jump(exit_jump);
+        self.emit_op(OpCode::OpPop);
+    }
+
+    fn for_statement(&mut self) {
+        self.begin_scope();
+        self.consume(TokenType::LeftParen, "Expect '(' after 'for'.");
+        if self.match_token(TokenType::Semicolon) {
+            // No initializer.
+        } else if self.match_token(TokenType::Var) {
+            self.var_declaration();
+        } else {
+            self.expression_statement();
+        }
+
+        let mut loop_start = self.chunk.code.len();
+        let mut exit_jump = None;
+        if !self.match_token(TokenType::Semicolon) {
+            self.expression();
+            self.consume(TokenType::Semicolon, "Expect ';' after loop condition.");
+            exit_jump = Some(self.emit_jump(OpCode::OpJumpIfFalse));
+            self.emit_op(OpCode::OpPop);
+        }
+
+        // The increment is compiled before the body but runs after it.
+        if !self.match_token(TokenType::RightParen) {
+            let body_jump = self.emit_jump(OpCode::OpJump);
+            let increment_start = self.chunk.code.len();
+            self.expression();
+            self.emit_op(OpCode::OpPop);
+            self.consume(TokenType::RightParen, "Expect ')' after for clauses.");
+            self.emit_loop(loop_start);
+            loop_start = increment_start;
+            self.patch_jump(body_jump);
+        }
+
+        self.statement();
+        self.emit_loop(loop_start);
+        if let Some(exit_jump) = exit_jump {
+            self.patch_jump(exit_jump);
+            self.emit_op(OpCode::OpPop);
+        }
+        self.end_scope();
+    }
+
+    fn block(&mut self) {
+        while !self.check(TokenType::RightBrace) && !self.check(TokenType::EOF) {
+            self.declaration();
+        }
+        self.consume(TokenType::RightBrace, "Expect '}' after block.");
+    }
+
+    fn begin_scope(&mut self) {
+        self.scope_depth += 1;
+    }
+
+    fn end_scope(&mut self) {
+        self.scope_depth -= 1;
+        while self
+            .locals
+            .last()
+            .is_some_and(|local| local.depth.is_none_or(|depth| depth > self.scope_depth))
+        {
+            self.emit_op(OpCode::OpPop);
+            self.locals.pop();
+        }
+    }
+
+    fn expression(&mut self) {
+        self.parse_precedence(Precedence::Assignment);
+    }
+
+    /// Compiles an expression whose operators bind at least as tightly as
+    /// `precedence`.
+    fn parse_precedence(&mut self, precedence: Precedence) {
+        self.advance();
+        let Some(prefix) = Self::get_rule(self.previous.token_type).prefix else {
+            self.error("Expect expression.");
+            return;
+        };
+        let can_assign = precedence <= Precedence::Assignment;
+        prefix(self, can_assign);
+
+        while precedence <= Self::get_rule(self.current.token_type).precedence {
+            self.advance();
+            if let Some(infix) = Self::get_rule(self.previous.token_type).infix {
+                infix(self, can_assign);
+            }
+        }
+        if can_assign && self.match_token(TokenType::Equal) {
+            self.error("Invalid assignment target.");
+        }
+    }
+
+    fn grouping(&mut self, _can_assign: bool) {
+        self.expression();
+        self.consume(TokenType::RightParen, "Expect ')' after expression.");
+    }
+
+    fn unary(&mut self, _can_assign: bool) {
+        let operator = self.previous.token_type;
+        self.parse_precedence(Precedence::Unary);
+        match operator {
+            TokenType::Bang => self.emit_op(OpCode::OpNot),
+            TokenType::Minus => self.emit_op(OpCode::OpNegate),
+            _ => unreachable!("not a unary operator: {}", operator),
+        }
+    }
+
+    fn binary(&mut self, _can_assign: bool) {
+        let operator = self.previous.token_type;
+        let precedence = Self::get_rule(operator).precedence;
+        self.parse_precedence(precedence.next());
+        match operator {
+            TokenType::BangEqual => self.emit_ops(OpCode::OpEqual, OpCode::OpNot),
+            TokenType::EqualEqual => self.emit_op(OpCode::OpEqual),
+            TokenType::Greater => self.emit_op(OpCode::OpGreater),
+            TokenType::GreaterEqual => self.emit_ops(OpCode::OpLess, OpCode::OpNot),
+            TokenType::Less => self.emit_op(OpCode::OpLess),
+            TokenType::LessEqual => self.emit_ops(OpCode::OpGreater, OpCode::OpNot),
+            TokenType::Plus => self.emit_op(OpCode::OpAdd),
+            TokenType::Minus => self.emit_op(OpCode::OpSubtract),
+            TokenType::Star => self.emit_op(OpCode::OpMultiply),
+            TokenType::Slash => self.emit_op(OpCode::OpDivide),
+            _ => unreachable!("not a binary operator: {}", operator),
+        }
+    }
+
+    /// The right operand is skipped when the left is falsey.
+    fn and(&mut self, _can_assign: bool) {
+        let end_jump = self.emit_jump(OpCode::OpJumpIfFalse);
+        self.emit_op(OpCode::OpPop);
+        self.parse_precedence(Precedence::And);
+        self.patch_jump(end_jump);
+    }
+
+    /// The right operand is skipped when the left is truthy.
+    fn or(&mut self, _can_assign: bool) {
+        let else_jump = self.emit_jump(OpCode::OpJumpIfFalse);
+        let end_jump = self.emit_jump(OpCode::OpJump);
+        self.patch_jump(else_jump);
+        self.emit_op(OpCode::OpPop);
+        self.parse_precedence(Precedence::Or);
+        self.patch_jump(end_jump);
+    }
+
+    /// Integer literals stay exact as `Value::Integer`; floats become `Value::Number`.
+    fn number(&mut self, _can_assign: bool) {
+        match self.previous.token_type {
+            TokenType::Integer(value) => self.emit_constant(Value::Integer(value)),
+            TokenType::Number(value) => self.emit_constant(Value::Number(value)),
+            _ => unreachable!("not a number: {}", self.previous),
+        }
+    }
+
+    /// Also compiles the segments of an interpolated string.
+    fn string(&mut self, _can_assign: bool) {
+        self.emit_constant(Value::String(self.previous.lexeme.clone()));
+    }
+
+    /// Compiles `"a ${x} b"` as `"a" + str(x) + " b"`.
+    fn interpolation(&mut self, can_assign: bool) {
+        self.string(can_assign);
+        loop {
+            self.expression();
+            self.emit_ops(OpCode::OpStringify, OpCode::OpAdd);
+            let more = self.match_token(TokenType::Interpolation);
+            if !more {
+                self.consume(
+                    TokenType::String,
+                    "Expect '}' after interpolated expression.",
+                );
+            }
+            self.string(can_assign);
+            self.emit_op(OpCode::OpAdd);
+            if !more {
+                return;
+            }
+        }
+    }
+
+    fn literal(&mut self, _can_assign: bool) {
+        match self.previous.token_type {
+            TokenType::False => self.emit_op(OpCode::OpFalse),
+            TokenType::Nil => self.emit_op(OpCode::OpNil),
+            TokenType::True => self.emit_op(OpCode::OpTrue),
+            _ => unreachable!("not a literal: {}", self.previous),
+        }
+    }
+
+    fn variable(&mut self, can_assign: bool) {
+        self.named_variable(self.previous.clone(), can_assign);
+    }
+
+    fn named_variable(&mut self, name: Token, can_assign: bool) {
+        let (get, set, operand) = match self.resolve_local(&name) {
+            Some(slot) => (OpCode::OpGetLocal, OpCode::OpSetLocal, Operand::Slot(slot)),
+            None => {
+                let index = self.identifier_constant(name.lexeme);
+                (
+                    OpCode::OpGetGlobal,
+                    OpCode::OpSetGlobal,
+                    Operand::Name(index),
+                )
+            }
+        };
+        if can_assign && self.match_token(TokenType::Equal) {
+            self.expression();
+            self.emit_op(set);
+        } else {
+            self.emit_op(get);
+        }
+        self.emit_operand(operand);
+    }
+
+    /// The stack slot of the innermost local called `name`, or `None` if it
+    /// is a global.
+    fn resolve_local(&mut self, name: &Token) -> Option<u8> {
+        let (slot, local) = self
+            .locals
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(_, local)| local.name == name.lexeme)?;
+        if local.depth.is_none() {
+            self.error_at(
+                name.clone(),
+                ErrorCode::Resolve,
+                "Can't read local variable in its own initializer.",
+            );
+        }
+        Some(slot as u8)
+    }
+
+    /// Consumes a variable name and declares it, returning its name constant
+    /// if it is a global.
+    fn parse_variable(&mut self, message: &str) -> usize {
+        self.consume(TokenType::Identifier, message);
+        self.declare_variable();
+        if self.scope_depth > 0 {
+            return 0;
+        }
+        self.identifier_constant(self.previous.lexeme.clone())
+    }
+
+    fn declare_variable(&mut self) {
+        if self.scope_depth == 0 {
+            return;
+        }
+        let name = self.previous.clone();
+        let shadows = self
+            .locals
+            .iter()
+            .rev()
+            .take_while(|local| local.depth.is_none_or(|depth| depth >= self.scope_depth))
+            .any(|local| local.name == name.lexeme);
+        if shadows {
+            self.error_at(
+                name.clone(),
+                ErrorCode::Resolve,
+                "Already a variable with this name in this scope.",
+            );
+        }
+        if self.locals.len() == MAX_LOCALS {
+            self.error("Too many local variables in function.");
+            return;
+        }
+        self.locals.push(Local {
+            name: name.lexeme,
+            depth: None,
+        });
+    }
+
+    fn define_variable(&mut self, global: usize) {
+        if self.scope_depth > 0 {
+            if let Some(local) = self.locals.last_mut() {
+                local.depth = Some(self.scope_depth);
+            }
+            return;
+        }
+        self.emit_op(OpCode::OpDefineGlobal);
+        self.emit_operand(Operand::Name(global));
+    }
+
+    fn identifier_constant(&mut self, name: Symbol) -> usize {
+        if let Some(&index) = self.names.get(&name) {
+            return index;
+        }
+        let index = self.make_constant(Value::String(name.clone()));
+        self.names.insert(name, index);
+        index
+    }
+
+    fn make_constant(&mut self, value: Value) -> usize {
+        self.chunk.add_constant(value).unwrap_or_else(|error| {
+            self.error(&error.to_string());
+            0
+        })
+    }
+
+    fn emit_op(&mut self, op: OpCode) {
+        self.chunk.write(op.into(), self.previous.span.line);
+    }
+
+    fn emit_ops(&mut self, first: OpCode, second: OpCode) {
+        self.emit_op(first);
+        self.emit_op(second);
+    }
+
+    fn emit_operand(&mut self, operand: Operand) {
+        let line = self.previous.span.line;
+        match operand {
+            Operand::Slot(slot) => self.chunk.write(slot, line),
+            Operand::Name(index) => self.chunk.write_u24(index, line),
+        }
+    }
+
+    fn emit_constant(&mut self, value: Value) {
+        if let Err(error) = self.chunk.write_constant(value, self.previous.span.line) {
+            self.error(&error.to_string());
+        }
+    }
+
+    /// Emits `op` with a placeholder distance and returns the operand's
+    /// offset for `patch_jump`.
+    fn emit_jump(&mut self, op: OpCode) -> usize {
+        self.emit_op(op);
+        let line = self.previous.span.line;
+        self.chunk.write(0xff, line);
+        self.chunk.write(0xff, line);
+        self.chunk.code.len() - 2
+    }
+
+    /// Points the jump at `offset` to the next instruction to be emitted.
+    fn patch_jump(&mut self, offset: usize) {
+        let Ok(distance) = u16::try_from(self.chunk.code.len() - offset - 2) else {
+            self.error("Too much code to jump over.");
+            return;
+        };
+        let [low, high] = distance.to_le_bytes();
+        self.chunk.patch(offset, low);
+        self.chunk.patch(offset + 1, high);
+    }
+
+    fn emit_loop(&mut self, loop_start: usize) {
+        self.emit_op(OpCode::OpLoop);
+        let Ok(distance) = u16::try_from(self.chunk.code.len() - loop_start + 2) else {
+            self.error("Loop body too large.");
+            return;
+        };
+        let line = self.previous.span.line;
+        for byte in distance.to_le_bytes() {
+            self.chunk.write(byte, line);
+        }
+    }
+
+    fn advance(&mut self) {
+        self.previous = self.current.clone();
+        loop {
+            self.current = self
+                .tokens
+                .next()
+                .unwrap_or_else(|| eof(self.previous.span));
+            if self.current.token_type != TokenType::Error {
+                break;
+            }
+            // The scanner has already reported its error tokens; panic so
+            // the statement's follow-on errors aren't reported too.
+            self.errors.had_error = true;
+            self.panic_mode = true;
+        }
+    }
+
+    fn check(&self, token_type: TokenType) -> bool {
+        discriminant(&self.current.token_type) == discriminant(&token_type)
+    }
+
+    fn match_token(&mut self, token_type: TokenType) -> bool {
+        if !self.check(token_type) {
+            return false;
+        }
+        self.advance();
+        true
+    }
+
+    fn consume(&mut self, token_type: TokenType, message: &str) {
+        if self.check(token_type) {
+            self.advance();
+        } else {
+            self.error_at(self.current.clone(), ErrorCode::Parse, message);
+        }
+    }
+
+    fn error(&mut self, message: &str) {
+        self.error_at(self.previous.clone(), ErrorCode::Parse, message);
+    }
+
+    fn error_at(&mut self, token: Token, code: ErrorCode, message: &str) {
+        if self.panic_mode {
+            return;
+        }
+        self.panic_mode = true;
+        self.errors.error_at(code, &token, message.to_string());
+    }
+
+    /// Skips to the start of the next statement after a syntax error.
+    fn synchronize(&mut self) {
+        self.panic_mode = false;
+        while self.current.token_type != TokenType::EOF {
+            if self.previous.token_type == TokenType::Semicolon {
+                return;
+            }
+            match self.current.token_type {
+                TokenType::Class
+                | TokenType::Fn
+                | TokenType::Var
+                | TokenType::For
+                | TokenType::If
+                | TokenType::While
+                | TokenType::Print
+                | TokenType::Return => return,
+                _ => self.advance(),
+            }
+        }
+    }
+}
+
+/// How a variable instruction finds its variable.
+enum Operand {
+    Slot(u8),
+    Name(usize),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::clox::memory::bytes_allocated;
+    use crate::clox::vm::{InterpretResult, VM};
+    use crate::scanner::Scanner;
+
+    /// Compiles and runs `code`, returning what it printed or the errors.
+    fn run(code: &str) -> Result<String, Vec<String>> {
+        let mut compiler = Compiler::new(Scanner::new(code.to_string(), Lox::new()), Lox::new());
+        let chunk = compiler.compile();
+        let errors = compiler.get_errors().errors;
+        if !errors.is_empty() {
+            return Err(errors.iter().map(|error| error.to_string()).collect());
+        }
+        let mut vm = VM::new(Vec::new());
+        match vm.interpret(&chunk) {
+            InterpretResult::Ok => Ok(String::from_utf8(vm.into_output()).unwrap()),
+            _ => Err(vec![vm.error.unwrap().to_string()]),
+        }
+    }
+
+    #[test]
+    fn test_expressions_and_precedence() {
+        assert_eq!(
+            run("print 1 + 2 * 3 - -4 / 2; print !(1 > 2) == true; print 3 >= 3 and 2 <= 1;"),
+            Ok("9\ntrue\nfalse\n".to_string())
+        );
+        assert_eq!(
+            run("print nil or \"a\" + \"b\"; print \"n = ${1 + 1}, ${nil}.\";"),
+            Ok("ab\nn = 2, nil.\n".to_string())
+        );
+    }
+
+    #[test]
+    fn test_integers_stay_exact_until_mixed_with_floats() {
+        assert_eq!(
+            run("print 9007199254740993; print 6 / 3; print 7 / 2; print 1 + 0.5; print 2 == 2.0; print 3 > 2.5;"),
+            Ok("9007199254740993\n2\n3.5\n1.5\ntrue\ntrue\n".to_string())
+        );
+        assert_eq!(
+            run("print 9223372036854775807 + 1;"),
+            Err(vec!["Integer overflow.\n[line 1] in script".to_string()])
+        );
+        assert_eq!(
+            run("var min = -9223372036854775807 - 1;\nprint -min;"),
+            Err(vec!["Integer overflow.\n[line 2] in script".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_runtime_strings_compare_by_content_and_are_freed() {
+        let before = bytes_allocated();
+        assert_eq!(
+            run("var a = \"a\" + \"b\"; print a == \"ab\"; print \"${1}\" == \"1\"; print a == 1;"),
+            Ok("true\ntrue\nfalse\n".to_string())
+        );
+        assert_eq!(bytes_allocated(), before);
+    }
+
+    #[test]
+    fn test_globals_locals_and_blocks() {
+        let code = "var a = 1; var b;
+            { var a = 2; var c = 3; { var d = c * a; b = d; } print a == 2; print c; }
+            a = a + 10; print a; print b;";
+        assert_eq!(run(code), Ok("true\n3\n11\n6\n".to_string()));
+        assert_eq!(
+            run("print x;"),
+            Err(vec![
+                "Undefined variable 'x'.\n[line 1] in script".to_string()
+            ])
+        );
+    }
+
+    #[test]
+    fn test_control_flow() {
+        let code = "var s = \"\";
+            for (var i = 0; i < 5; i = i + 1) { if (i == 2) s = s + \"two \"; else s = s + \"${i} \"; }
+            var n = 10; while (n > 1) n = n / 2;
+            print s; print n;
+            for (;false;) print \"never\";";
+        assert_eq!(run(code), Ok("0 1 two 3 4 \n0.625\n".to_string()));
+    }
+
+    #[test]
+    fn test_compile_errors() {
+        assert_eq!(
+            run("print 1 +;\nvar 1;\n{ var a = 1; var a = 2; }\n{ var b = b; }\n1 = 2;"),
+            Err(vec![
+                "1 at ';' Expect expression.".to_string(),
+                "2 at '1' Expect variable name.".to_string(),
+                "3 at 'a' Already a variable with this name in this scope.".to_string(),
+                "4 at 'b' Can't read local variable in its own initializer.".to_string(),
+                "5 at '=' Invalid assignment target.".to_string(),
+            ])
+        );
+        assert_eq!(
+            run("fn f() { if (true) { return 1; } }\nreturn 2;\nprint 3;"),
+            Err(vec![
+                "1 at 'fn' Functions are not supported by the bytecode VM yet.".to_string(),
+                "2 at 'return' Can't return from top-level code.".to_string(),
+            ])
+        );
+    }
+}
@@ -6,42 +6,137 @@ pub fn disassemble_chunk(chunk: &Chunk, name: &str) {
     println!("== {} ==", name);
 
     let mut offset = 0;
-    while offset < chunk.count {
+    while offset < chunk.code.len() {
         offset = disassemble_instruction(chunk, offset);
     }
 }
 
 pub fn disassemble_instruction(chunk: &Chunk, offset: usize) -> usize {
-    println!("{:04}", offset);
-
-    unsafe {
-        let instruction = chunk.code.add(offset) as u8;
-        match OpCode::try_from(instruction).unwrap() {
-            OpCode::OpReturn => simple_instruction("OP_RETURN", offset),
-            OpCode::Unknown => {
-                println!("Unknown opcode {}", instruction);
-                offset + 1
-            }
+    let (text, next) = instruction_text(chunk, offset);
+    println!("{}", text);
+    next
+}
+
+/// Formats the instruction at `offset` and returns it with the offset of the
+/// next one.
+fn instruction_text(chunk: &Chunk, offset: usize) -> (String, usize) {
+    let instruction = chunk.read(offset);
+    let (text, next) = match OpCode::from(instruction) {
+        OpCode::OpConstant => {
+            let index = chunk.read(offset + 1) as usize;
+            constant_instruction("OP_CONSTANT", chunk, index, offset + 2)
         }
-    }
+        OpCode::OpConstantLong => {
+            let index = chunk.read_u24(offset + 1);
+            constant_instruction("OP_CONSTANT_LONG", chunk, index, offset + 4)
+        }
+        OpCode::OpDefineGlobal => global_instruction("OP_DEFINE_GLOBAL", chunk, offset),
+        OpCode::OpGetGlobal => global_instruction("OP_GET_GLOBAL", chunk, offset),
+        OpCode::OpSetGlobal => global_instruction("OP_SET_GLOBAL", chunk, offset),
+        OpCode::OpGetLocal => byte_instruction("OP_GET_LOCAL", chunk, offset),
+        OpCode::OpSetLocal => byte_instruction("OP_SET_LOCAL", chunk, offset),
+        OpCode::OpJump => jump_instruction("OP_JUMP", true, chunk, offset),
+        OpCode::OpJumpIfFalse => jump_instruction("OP_JUMP_IF_FALSE", true, chunk, offset),
+        OpCode::OpLoop => jump_instruction("OP_LOOP", false, chunk, offset),
+        OpCode::OpReturn => simple_instruction("OP_RETURN", offset),
+        OpCode::OpNil => simple_instruction("OP_NIL", offset),
+        OpCode::OpTrue => simple_instruction("OP_TRUE", offset),
+        OpCode::OpFalse => simple_instruction("OP_FALSE", offset),
+        OpCode::OpEqual => simple_instruction("OP_EQUAL", offset),
+        OpCode::OpGreater => simple_instruction("OP_GREATER", offset),
+        OpCode::OpLess => simple_instruction("OP_LESS", offset),
+        OpCode::OpAdd => simple_instruction("OP_ADD", offset),
+        OpCode::OpSubtract => simple_instruction("OP_SUBTRACT", offset),
+        OpCode::OpMultiply => simple_instruction("OP_MULTIPLY", offset),
+        OpCode::OpDivide => simple_instruction("OP_DIVIDE", offset),
+        OpCode::OpNot => simple_instruction("OP_NOT", offset),
+        OpCode::OpNegate => simple_instruction("OP_NEGATE", offset),
+        OpCode::OpPrint => simple_instruction("OP_PRINT", offset),
+        OpCode::OpPop => simple_instruction("OP_POP", offset),
+        OpCode::OpStringify => simple_instruction("OP_STRINGIFY", offset),
+        OpCode::Unknown => (format!("Unknown opcode {}", instruction), offset + 1),
+    };
+    let line = if offset > 0 && chunk.get_line(offset) == chunk.get_line(offset - 1) {
+        "   |".to_string()
+    } else {
+        format!("{:4}", chunk.get_line(offset))
+    };
+    (format!("{:04} {} {}", offset, line, text), next)
+}
+
+fn simple_instruction(name: &str, offset: usize) -> (String, usize) {
+    (name.to_string(), offset + 1)
+}
+
+fn byte_instruction(name: &str, chunk: &Chunk, offset: usize) -> (String, usize) {
+    let slot = chunk.read(offset + 1);
+    (format!("{:<16} {:4}", name, slot), offset + 2)
+}
+
+fn global_instruction(name: &str, chunk: &Chunk, offset: usize) -> (String, usize) {
+    let index = chunk.read_u24(offset + 1);
+    constant_instruction(name, chunk, index, offset + 4)
 }
 
-fn simple_instruction(name: &str, offset: usize) -> usize {
-    println!("{}", name);
-    offset + 1
+/// Shows a jump's operand and the offset it lands on.
+fn jump_instruction(name: &str, forwards: bool, chunk: &Chunk, offset: usize) -> (String, usize) {
+    let distance = chunk.read_u16(offset + 1) as usize;
+    let next = offset + 3;
+    let target = if forwards {
+        next + distance
+    } else {
+        next - distance
+    };
+    (format!("{:<16} {:4} -> {}", name, offset, target), next)
+}
+
+fn constant_instruction(name: &str, chunk: &Chunk, index: usize, next: usize) -> (String, usize) {
+    (
+        format!("{:<16} {:4} '{}'", name, index, chunk.constants[index]),
+        next,
+    )
 }
 
 #[cfg(test)]
 mod test {
     use crate::clox::chunk::{Chunk, Chunkable, OpCode};
 
-    use super::disassemble_chunk;
+    use crate::clox::value::Value;
+
+    use super::{disassemble_chunk, instruction_text};
 
     #[test]
     fn test_disassemble_chunk() {
         let mut chunk = Chunk::init();
-        chunk.write(OpCode::OpReturn.into());
+        chunk.write(OpCode::OpReturn.into(), 1);
         disassemble_chunk(&chunk, "test chunk");
-        chunk.free();
+    }
+
+    #[test]
+    fn test_constant_instructions_show_index_and_value() {
+        let mut chunk = Chunk::init();
+        for i in 0..257 {
+            chunk
+                .write_constant(Value::Number(i as f64 + 0.5), 1)
+                .unwrap();
+        }
+        chunk.write(OpCode::OpReturn.into(), 2);
+
+        assert_eq!(
+            instruction_text(&chunk, 0),
+            ("0000    1 OP_CONSTANT         0 '0.5'".to_string(), 2)
+        );
+        assert_eq!(
+            instruction_text(&chunk, 2),
+            ("0002    | OP_CONSTANT         1 '1.5'".to_string(), 4)
+        );
+        assert_eq!(
+            instruction_text(&chunk, 512),
+            ("0512    | OP_CONSTANT_LONG  256 '256.5'".to_string(), 516)
+        );
+        assert_eq!(
+            instruction_text(&chunk, 516),
+            ("0516    2 OP_RETURN".to_string(), 517)
+        );
     }
 }
@@ -1,9 +1,14 @@
 use std::{
-    alloc::{realloc, Layout},
-    process,
-    ptr::null_mut,
+    cell::Cell,
+    marker::PhantomData,
+    mem::size_of,
+    ops::{Index, IndexMut},
 };
 
+thread_local! {
+    static BYTES_ALLOCATED: Cell<usize> = const { Cell::new(0) };
+}
+
 pub fn grow_capacity(capacity: usize) -> usize {
     if capacity < 8 {
         8
@@ -11,20 +16,160 @@ pub fn grow_capacity(capacity: usize) -> usize {
         capacity * 2
     }
 }
-pub fn reallocate(code: *mut u8, old_size: usize, new_size: usize) -> *mut u8 {
-    let layout = Layout::array::<u8>(old_size).unwrap();
-    if new_size == 0 {
-        unsafe { std::alloc::dealloc(code, layout) }
-        return null_mut();
+
+/// Bytes currently held by every `DynArray` and `ObjString` on this thread,
+/// for deciding when to collect garbage.
+#[allow(dead_code)] // Read by the garbage collector once there is one.
+pub fn bytes_allocated() -> usize {
+    BYTES_ALLOCATED.with(Cell::get)
+}
+
+/// Records an allocation changing size from `old_size` to `new_size` bytes.
+/// The count saturates rather than wrapping if it is ever out of step.
+pub(super) fn track(old_size: usize, new_size: usize) {
+    BYTES_ALLOCATED.with(|bytes| {
+        bytes.set(
+            bytes
+                .get()
+                .saturating_sub(old_size)
+                .saturating_add(new_size),
+        )
+    });
+}
+
+/// Keeps a tracked allocation on the thread whose count it was added to.
+/// Freeing it on another thread would take it off the wrong count.
+pub(super) type NotSend = PhantomData<*const ()>;
+
+/// A growable array that grows by `grow_capacity` and reports its
+/// allocations to `bytes_allocated`, so it is not `Send`. Indexing past the
+/// end panics.
+pub struct DynArray<T> {
+    items: Vec<T>,
+    capacity: usize,
+    _thread: NotSend,
+}
+
+impl<T> DynArray<T> {
+    pub fn new() -> DynArray<T> {
+        DynArray {
+            items: Vec::new(),
+            capacity: 0,
+            _thread: PhantomData,
+        }
     }
 
-    let ptr;
-    unsafe {
-        ptr = realloc(code, layout, new_size);
+    pub fn push(&mut self, item: T) {
+        if self.capacity < self.items.len() + 1 {
+            let old_capacity = self.capacity;
+            self.capacity = grow_capacity(self.capacity);
+            self.items.reserve_exact(self.capacity - self.items.len());
+            track(
+                old_capacity * size_of::<T>(),
+                self.capacity * size_of::<T>(),
+            );
+        }
+        self.items.push(item);
+    }
+
+    pub fn last(&self) -> Option<&T> {
+        self.items.last()
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    #[cfg(test)]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        &self.items
+    }
+}
+
+impl<T> Default for DynArray<T> {
+    fn default() -> DynArray<T> {
+        DynArray::new()
     }
-    if ptr == null_mut() {
-        println!("Failed to realloc space");
-        process::exit(1);
+}
+
+impl<T> Index<usize> for DynArray<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        &self.items[index]
+    }
+}
+
+impl<T> IndexMut<usize> for DynArray<T> {
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        &mut self.items[index]
+    }
+}
+
+impl<T> Drop for DynArray<T> {
+    fn drop(&mut self) {
+        track(self.capacity * size_of::<T>(), 0);
+    }
+}
+
+// These also run under Miri: `cargo +nightly miri test clox`.
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_grows_by_policy_and_tracks_bytes() {
+        let before = bytes_allocated();
+        let mut array = DynArray::new();
+        assert_eq!(array.capacity(), 0);
+        for i in 0..9u32 {
+            array.push(i);
+        }
+        assert_eq!(array.len(), 9);
+        assert_eq!(array.capacity(), 16);
+        assert_eq!(bytes_allocated() - before, 16 * size_of::<u32>());
+        assert_eq!(array[8], 8);
+
+        drop(array);
+        assert_eq!(bytes_allocated(), before);
+    }
+
+    #[test]
+    fn test_drops_items_and_releases_bytes() {
+        let before = bytes_allocated();
+        let shared = Rc::new(());
+        {
+            let mut array = DynArray::new();
+            for _ in 0..20 {
+                array.push(Rc::clone(&shared));
+            }
+            array[3] = Rc::clone(&shared);
+            assert_eq!(Rc::strong_count(&shared), 21);
+        }
+        assert_eq!(Rc::strong_count(&shared), 1);
+        assert_eq!(bytes_allocated(), before);
+    }
+
+    #[test]
+    fn test_count_saturates_instead_of_wrapping() {
+        track(bytes_allocated() + 1, 0);
+        assert_eq!(bytes_allocated(), 0);
+        track(0, usize::MAX);
+        track(0, 1);
+        assert_eq!(bytes_allocated(), usize::MAX);
+        track(usize::MAX, 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_index_out_of_bounds_panics() {
+        let mut array = DynArray::new();
+        array.push(1u8);
+        let _ = array[1];
     }
-    return ptr;
 }
@@ -1,3 +1,9 @@
 pub mod chunk;
+pub mod compiler;
+// Kept for tracing execution while working on the VM.
+#[allow(dead_code)]
 pub mod debug;
 pub mod memory;
+pub mod object;
+pub mod value;
+pub mod vm;
@@ -0,0 +1,53 @@
+use std::{fmt::Display, marker::PhantomData};
+
+use super::memory::{track, NotSend};
+
+/// A string built while the program runs, e.g. by concatenation. Its
+/// characters are counted in `bytes_allocated` until it is dropped.
+#[derive(Debug)]
+pub struct ObjString {
+    chars: Box<str>,
+    _thread: NotSend,
+}
+
+impl ObjString {
+    pub fn new(chars: &str) -> ObjString {
+        track(0, chars.len());
+        ObjString {
+            chars: chars.into(),
+            _thread: PhantomData,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.chars
+    }
+}
+
+impl Display for ObjString {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.chars)
+    }
+}
+
+impl Drop for ObjString {
+    fn drop(&mut self) {
+        track(self.chars.len(), 0);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::clox::memory::bytes_allocated;
+
+    #[test]
+    fn test_counts_its_characters_until_dropped() {
+        let before = bytes_allocated();
+        let string = ObjString::new("hello");
+        assert_eq!(bytes_allocated() - before, 5);
+        assert_eq!(string.as_str(), "hello");
+        drop(string);
+        assert_eq!(bytes_allocated(), before);
+    }
+}
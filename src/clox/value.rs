@@ -0,0 +1,78 @@
+use std::{fmt::Display, rc::Rc};
+
+use super::memory::DynArray;
+use super::object::ObjString;
+use crate::token::Symbol;
+
+#[derive(Clone, Debug)]
+pub enum Value {
+    Bool(bool),
+    Nil,
+    /// Exact 64-bit arithmetic, as in the tree-walking interpreter.
+    Integer(i64),
+    Number(f64),
+    /// A string constant or global name. Constants with the same text share
+    /// one allocation, so comparing those is a pointer check.
+    String(Symbol),
+    /// A string built at runtime. It compares equal to a `String` with the
+    /// same text.
+    ObjString(Rc<ObjString>),
+}
+
+impl Value {
+    /// `nil` and `false` are falsey; every other value is truthy.
+    pub fn is_falsey(&self) -> bool {
+        matches!(self, Value::Nil | Value::Bool(false))
+    }
+
+    /// Numbers of either kind, promoting integers to floats.
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            Value::Integer(value) => Some(*value as f64),
+            Value::Number(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// The text of either kind of string.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(value) => Some(value.as_str()),
+            Value::ObjString(value) => Some(value.as_str()),
+            _ => None,
+        }
+    }
+}
+
+/// Integers and floats compare by value.
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Nil, Value::Nil) => true,
+            (Value::Integer(a), Value::Integer(b)) => a == b,
+            (Value::Number(a), Value::Number(b)) => a == b,
+            (Value::Integer(a), Value::Number(b)) | (Value::Number(b), Value::Integer(a)) => {
+                *a as f64 == *b
+            }
+            (Value::String(a), Value::String(b)) => a == b,
+            _ => matches!((self.as_str(), other.as_str()), (Some(a), Some(b)) if a == b),
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Nil => write!(f, "nil"),
+            Value::Integer(value) => write!(f, "{}", value),
+            Value::Number(value) => write!(f, "{}", value),
+            Value::String(value) => write!(f, "{}", value),
+            Value::ObjString(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// A chunk's constant pool.
+pub type ValueArray = DynArray<Value>;
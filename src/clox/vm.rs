@@ -0,0 +1,409 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    io::{self, Write},
+    rc::Rc,
+};
+
+use super::chunk::{Chunk, OpCode};
+use super::object::ObjString;
+use super::value::Value;
+use crate::token::Symbol;
+
+#[derive(Debug, PartialEq)]
+pub enum InterpretResult {
+    Ok,
+    RuntimeError,
+}
+
+/// A runtime error and the line of the instruction that raised it.
+#[derive(Debug, PartialEq)]
+pub struct VmError {
+    pub message: String,
+    pub line: usize,
+}
+
+impl Display for VmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}\n[line {}] in script", self.message, self.line)
+    }
+}
+
+/// Executes chunks on a value stack, writing `print` output to `out`.
+/// Globals outlive each chunk, so a REPL can keep one VM across lines.
+pub struct VM<W: Write> {
+    ip: usize,
+    stack: Vec<Value>,
+    globals: HashMap<Symbol, Value>,
+    out: W,
+    /// Set when `interpret` returns `RuntimeError`.
+    pub error: Option<VmError>,
+}
+
+impl VM<io::Stdout> {
+    pub fn stdout() -> VM<io::Stdout> {
+        VM::new(io::stdout())
+    }
+}
+
+impl<W: Write> VM<W> {
+    pub fn new(out: W) -> VM<W> {
+        VM {
+            ip: 0,
+            stack: Vec::new(),
+            globals: HashMap::new(),
+            out,
+            error: None,
+        }
+    }
+
+    /// Gives back the writer `print` output went to.
+    #[cfg(test)]
+    pub fn into_output(self) -> W {
+        self.out
+    }
+
+    pub fn interpret(&mut self, chunk: &Chunk) -> InterpretResult {
+        self.ip = 0;
+        self.error = None;
+        let result = self.run(chunk);
+        self.stack.clear();
+        result.err().unwrap_or(InterpretResult::Ok)
+    }
+
+    fn run(&mut self, chunk: &Chunk) -> Result<(), InterpretResult> {
+        while self.ip < chunk.code.len() {
+            let offset = self.ip;
+            match OpCode::from(self.read_byte(chunk)) {
+                OpCode::OpConstant => {
+                    let index = self.read_byte(chunk) as usize;
+                    self.push(chunk.constants[index].clone());
+                }
+                OpCode::OpConstantLong => {
+                    let index = self.read_u24(chunk);
+                    self.push(chunk.constants[index].clone());
+                }
+                OpCode::OpNil => self.push(Value::Nil),
+                OpCode::OpTrue => self.push(Value::Bool(true)),
+                OpCode::OpFalse => self.push(Value::Bool(false)),
+                OpCode::OpEqual => {
+                    let b = self.pop();
+                    let a = self.pop();
+                    self.push(Value::Bool(a == b));
+                }
+                OpCode::OpGreater => self.binary(
+                    chunk,
+                    offset,
+                    |a, b| Some(Value::Bool(a > b)),
+                    |a, b| Value::Bool(a > b),
+                )?,
+                OpCode::OpLess => self.binary(
+                    chunk,
+                    offset,
+                    |a, b| Some(Value::Bool(a < b)),
+                    |a, b| Value::Bool(a < b),
+                )?,
+                OpCode::OpAdd => match (self.peek(1), self.peek(0)) {
+                    (a, b) if a.as_str().is_some() && b.as_str().is_some() => {
+                        let joined = ObjString::new(&format!("{}{}", a, b));
+                        self.pop();
+                        self.pop();
+                        self.push(Value::ObjString(Rc::new(joined)));
+                    }
+                    (a, b) if a.as_number().is_some() && b.as_number().is_some() => self.binary(
+                        chunk,
+                        offset,
+                        |a, b| a.checked_add(b).map(Value::Integer),
+                        |a, b| Value::Number(a + b),
+                    )?,
+                    _ => {
+                        let message = "Operands must be two numbers or two strings.";
+                        return Err(self.runtime_error(chunk, offset, message));
+                    }
+                },
+                OpCode::OpSubtract => self.binary(
+                    chunk,
+                    offset,
+                    |a, b| a.checked_sub(b).map(Value::Integer),
+                    |a, b| Value::Number(a - b),
+                )?,
+                OpCode::OpMultiply => self.binary(
+                    chunk,
+                    offset,
+                    |a, b| a.checked_mul(b).map(Value::Integer),
+                    |a, b| Value::Number(a * b),
+                )?,
+                OpCode::OpDivide => {
+                    self.binary(chunk, offset, integer_divide, |a, b| Value::Number(a / b))?
+                }
+                OpCode::OpNot => {
+                    let value = self.pop();
+                    self.push(Value::Bool(value.is_falsey()));
+                }
+                OpCode::OpNegate => match self.pop() {
+                    Value::Integer(value) => match value.checked_neg() {
+                        Some(value) => self.push(Value::Integer(value)),
+                        None => return Err(self.runtime_error(chunk, offset, "Integer overflow.")),
+                    },
+                    Value::Number(value) => self.push(Value::Number(-value)),
+                    _ => {
+                        return Err(self.runtime_error(chunk, offset, "Operand must be a number."))
+                    }
+                },
+                OpCode::OpPrint => {
+                    let value = self.pop();
+                    // A script has no way to observe a failed write.
+                    let _ = writeln!(self.out, "{}", value);
+                }
+                OpCode::OpPop => {
+                    self.pop();
+                }
+                OpCode::OpDefineGlobal => {
+                    let name = self.read_name(chunk);
+                    let value = self.pop();
+                    self.globals.insert(name, value);
+                }
+                OpCode::OpGetGlobal => {
+                    let name = self.read_name(chunk);
+                    let Some(value) = self.globals.get(&name).cloned() else {
+                        let message = format!("Undefined variable '{}'.", name);
+                        return Err(self.runtime_error(chunk, offset, &message));
+                    };
+                    self.push(value);
+                }
+                OpCode::OpSetGlobal => {
+                    let name = self.read_name(chunk);
+                    let value = self.peek(0).clone();
+                    let Some(global) = self.globals.get_mut(&name) else {
+                        let message = format!("Undefined variable '{}'.", name);
+                        return Err(self.runtime_error(chunk, offset, &message));
+                    };
+                    *global = value;
+                }
+                OpCode::OpGetLocal => {
+                    let slot = self.read_byte(chunk) as usize;
+                    self.push(self.stack[slot].clone());
+                }
+                OpCode::OpSetLocal => {
+                    let slot = self.read_byte(chunk) as usize;
+                    self.stack[slot] = self.peek(0).clone();
+                }
+                OpCode::OpJump => {
+                    let distance = self.read_u16(chunk);
+                    self.ip += distance;
+                }
+                OpCode::OpJumpIfFalse => {
+                    let distance = self.read_u16(chunk);
+                    if self.peek(0).is_falsey() {
+                        self.ip += distance;
+                    }
+                }
+                OpCode::OpLoop => {
+                    let distance = self.read_u16(chunk);
+                    self.ip -= distance;
+                }
+                OpCode::OpStringify => {
+                    let value = self.pop();
+                    let text = match value.as_str() {
+                        Some(_) => value,
+                        None => Value::ObjString(Rc::new(ObjString::new(&value.to_string()))),
+                    };
+                    self.push(text);
+                }
+                OpCode::OpReturn => return Ok(()),
+                OpCode::Unknown => {
+                    return Err(self.runtime_error(chunk, offset, "Unknown opcode."))
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn read_byte(&mut self, chunk: &Chunk) -> u8 {
+        let byte = chunk.read(self.ip);
+        self.ip += 1;
+        byte
+    }
+
+    fn read_u16(&mut self, chunk: &Chunk) -> usize {
+        let value = chunk.read_u16(self.ip);
+        self.ip += 2;
+        value as usize
+    }
+
+    fn read_u24(&mut self, chunk: &Chunk) -> usize {
+        let value = chunk.read_u24(self.ip);
+        self.ip += 3;
+        value
+    }
+
+    /// Reads a global operand: the constant holding the variable's name.
+    fn read_name(&mut self, chunk: &Chunk) -> Symbol {
+        match &chunk.constants[self.read_u24(chunk)] {
+            Value::String(name) => name.clone(),
+            value => panic!("global name {} is not a string", value),
+        }
+    }
+
+    fn push(&mut self, value: Value) {
+        self.stack.push(value);
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("stack underflow")
+    }
+
+    /// The value `distance` slots below the top of the stack.
+    fn peek(&self, distance: usize) -> &Value {
+        &self.stack[self.stack.len() - 1 - distance]
+    }
+
+    /// Pops two number operands and pushes the result of applying them to
+    /// `integer_op` if both are integers, or else to `float_op`.
+    /// `integer_op` gives `None` on overflow.
+    fn binary(
+        &mut self,
+        chunk: &Chunk,
+        offset: usize,
+        integer_op: impl Fn(i64, i64) -> Option<Value>,
+        float_op: impl Fn(f64, f64) -> Value,
+    ) -> Result<(), InterpretResult> {
+        let b = self.pop();
+        let a = self.pop();
+        let result = match (&a, &b) {
+            (Value::Integer(a), Value::Integer(b)) => integer_op(*a, *b).ok_or("Integer overflow."),
+            _ => match (a.as_number(), b.as_number()) {
+                (Some(a), Some(b)) => Ok(float_op(a, b)),
+                _ => Err("Operands must be numbers."),
+            },
+        };
+        match result {
+            Ok(value) => {
+                self.push(value);
+                Ok(())
+            }
+            Err(message) => Err(self.runtime_error(chunk, offset, message)),
+        }
+    }
+
+    /// Records `message` against the line of the instruction at `offset`.
+    fn runtime_error(&mut self, chunk: &Chunk, offset: usize, message: &str) -> InterpretResult {
+        self.error = Some(VmError {
+            message: message.to_string(),
+            line: chunk.get_line(offset),
+        });
+        InterpretResult::RuntimeError
+    }
+}
+
+/// Integer division stays exact when it can; otherwise it gives a float.
+fn integer_divide(a: i64, b: i64) -> Option<Value> {
+    match a.checked_rem(b) {
+        Some(0) => a.checked_div(b).map(Value::Integer),
+        Some(_) => Some(Value::Number(a as f64 / b as f64)),
+        None if b == 0 => Some(Value::Number(a as f64 / b as f64)),
+        None => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::clox::chunk::Chunkable;
+
+    /// Runs `chunk`, returning the result and what it printed.
+    fn run(chunk: &Chunk) -> (InterpretResult, String, Option<String>) {
+        let mut vm = VM::new(Vec::new());
+        let result = vm.interpret(chunk);
+        let error = vm.error.map(|error| error.to_string());
+        (result, String::from_utf8(vm.out).unwrap(), error)
+    }
+
+    fn write_ops(chunk: &mut Chunk, ops: Vec<OpCode>, line: usize) {
+        ops.into_iter().for_each(|op| chunk.write(op.into(), line));
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        // print -((1.2 + 3.4) / 2) * 10;
+        let mut chunk = Chunk::init();
+        chunk.write_constant(Value::Number(1.2), 1).unwrap();
+        chunk.write_constant(Value::Number(3.4), 1).unwrap();
+        chunk.write(OpCode::OpAdd.into(), 1);
+        chunk.write_constant(Value::Number(2.0), 1).unwrap();
+        write_ops(&mut chunk, vec![OpCode::OpDivide, OpCode::OpNegate], 1);
+        chunk.write_constant(Value::Number(10.0), 1).unwrap();
+        write_ops(&mut chunk, vec![OpCode::OpMultiply, OpCode::OpPrint], 1);
+        chunk.write_constant(Value::Number(5.0), 1).unwrap();
+        chunk.write_constant(Value::Number(3.0), 1).unwrap();
+        write_ops(&mut chunk, vec![OpCode::OpSubtract, OpCode::OpPrint], 1);
+        chunk.write(OpCode::OpReturn.into(), 1);
+
+        assert_eq!(
+            run(&chunk),
+            (InterpretResult::Ok, "-23\n2\n".to_string(), None)
+        );
+    }
+
+    #[test]
+    fn test_comparison_and_logic() {
+        // print !(5 - 4 > 3 * 2 == !nil);
+        let mut chunk = Chunk::init();
+        chunk.write_constant(Value::Number(5.0), 1).unwrap();
+        chunk.write_constant(Value::Number(4.0), 1).unwrap();
+        chunk.write(OpCode::OpSubtract.into(), 1);
+        chunk.write_constant(Value::Number(3.0), 1).unwrap();
+        chunk.write_constant(Value::Number(2.0), 1).unwrap();
+        write_ops(&mut chunk, vec![OpCode::OpMultiply, OpCode::OpGreater], 1);
+        write_ops(
+            &mut chunk,
+            vec![OpCode::OpNil, OpCode::OpNot, OpCode::OpEqual],
+            1,
+        );
+        write_ops(&mut chunk, vec![OpCode::OpNot, OpCode::OpPrint], 1);
+        // print 1 < 2; print true == false;
+        chunk.write_constant(Value::Number(1.0), 2).unwrap();
+        chunk.write_constant(Value::Number(2.0), 2).unwrap();
+        write_ops(&mut chunk, vec![OpCode::OpLess, OpCode::OpPrint], 2);
+        let ops = vec![
+            OpCode::OpTrue,
+            OpCode::OpFalse,
+            OpCode::OpEqual,
+            OpCode::OpPrint,
+        ];
+        write_ops(&mut chunk, ops, 2);
+
+        assert_eq!(
+            run(&chunk),
+            (InterpretResult::Ok, "true\ntrue\nfalse\n".to_string(), None)
+        );
+    }
+
+    #[test]
+    fn test_type_error_reports_line() {
+        let mut chunk = Chunk::init();
+        chunk.write_constant(Value::Number(1.0), 1).unwrap();
+        chunk.write(OpCode::OpPrint.into(), 1);
+        chunk.write_constant(Value::Number(1.0), 2).unwrap();
+        chunk.write(OpCode::OpTrue.into(), 3);
+        chunk.write(OpCode::OpAdd.into(), 3);
+        chunk.write(OpCode::OpNil.into(), 4);
+        chunk.write(OpCode::OpNegate.into(), 4);
+
+        let (result, out, error) = run(&chunk);
+        assert_eq!(result, InterpretResult::RuntimeError);
+        assert_eq!(out, "1\n");
+        assert_eq!(
+            error.as_deref(),
+            Some("Operands must be two numbers or two strings.\n[line 3] in script")
+        );
+
+        let mut chunk = Chunk::init();
+        write_ops(&mut chunk, vec![OpCode::OpNil, OpCode::OpNegate], 7);
+        let (_, _, error) = run(&chunk);
+        assert_eq!(
+            error.as_deref(),
+            Some("Operand must be a number.\n[line 7] in script")
+        );
+    }
+}
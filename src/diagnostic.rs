@@ -0,0 +1,100 @@
+use crate::token::Span;
+
+/// Quotes the line of `source` containing `span` and underlines the span with
+/// carets. Spans running past the end of the line are clipped to it.
+///
+/// ```text
+///   3 | print a + "b";
+///     |           ^^^
+/// ```
+pub fn render(source: &str, span: Span) -> String {
+    let start = span.start.min(source.len());
+    let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = source[start..]
+        .find('\n')
+        .map_or(source.len(), |i| start + i);
+    let text = source[line_start..line_end].trim_end_matches('\r');
+    let end = span.end.clamp(start, line_end);
+
+    let number = span.line.to_string();
+    let gutter = " ".repeat(number.len());
+    // Tabs are kept so the carets line up however wide the terminal shows them.
+    let padding: String = source[line_start..start]
+        .chars()
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    let carets = "^".repeat(source[start..end].chars().count().max(1));
+    format!(
+        "  {} | {}\n  {} | {}{}\n",
+        number, text, gutter, padding, carets
+    )
+}
+
+/// The span of line `line` (1-based) of `source`, without its newline.
+pub fn line_span(source: &str, line: usize) -> Span {
+    let start = match line {
+        0 | 1 => 0,
+        _ => source
+            .match_indices('\n')
+            .nth(line - 2)
+            .map_or(source.len(), |(i, _)| i + 1),
+    };
+    let end = source[start..]
+        .find('\n')
+        .map_or(source.len(), |i| start + i);
+    Span {
+        start,
+        end,
+        line,
+        column: 1,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn span(start: usize, end: usize, line: usize) -> Span {
+        Span {
+            start,
+            end,
+            line,
+            column: 0,
+        }
+    }
+
+    #[test]
+    fn test_underlines_span() {
+        let source = "var a = 1;\nprint a + \"b\";\n";
+        let rendered = render(source, span(21, 24, 2));
+        assert_eq!(rendered, "  2 | print a + \"b\";\n    |           ^^^\n");
+    }
+
+    #[test]
+    fn test_clips_multiline_span_and_marks_empty_span() {
+        let source = "print \"a\nb\";";
+        assert_eq!(
+            render(source, span(6, 11, 1)),
+            "  1 | print \"a\n    |       ^^\n"
+        );
+        assert_eq!(render(source, span(12, 12, 2)), "  2 | b\";\n    |    ^\n");
+    }
+
+    #[test]
+    fn test_tab_indented_line() {
+        let source = "if (x) {\n\t\tprint -\"a\";\n}";
+        assert_eq!(
+            render(source, span(18, 21, 2)),
+            "  2 | \t\tprint -\"a\";\n    | \t\t       ^^^\n"
+        );
+    }
+
+    #[test]
+    fn test_line_span_covers_whole_line() {
+        let source = "var a = 1;\nprint -a;\n";
+        let span = line_span(source, 2);
+        assert_eq!(&source[span.start..span.end], "print -a;");
+        assert_eq!(render(source, span), "  2 | print -a;\n    | ^^^^^^^^^\n");
+        assert_eq!(line_span(source, 1).end, 10);
+    }
+}
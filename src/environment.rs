@@ -0,0 +1,70 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use crate::ast::Object;
+
+#[derive(Default)]
+pub struct Environment {
+    values: HashMap<String, Object>,
+    enclosing: Option<Rc<RefCell<Environment>>>,
+}
+
+impl Environment {
+    pub fn new() -> Rc<RefCell<Environment>> {
+        Rc::new(RefCell::new(Environment::default()))
+    }
+
+    pub fn new_enclosing(enclosing: &Rc<RefCell<Environment>>) -> Rc<RefCell<Environment>> {
+        Rc::new(RefCell::new(Environment {
+            values: HashMap::new(),
+            enclosing: Some(Rc::clone(enclosing)),
+        }))
+    }
+
+    pub fn define(&mut self, name: String, value: Object) {
+        self.values.insert(name, value);
+    }
+
+    /// Reads `name` from the scope `distance` hops up the chain, as computed
+    /// by the resolver.
+    pub fn get_at(&self, distance: usize, name: &str) -> Option<Object> {
+        if distance == 0 {
+            return self.values.get(name).cloned();
+        }
+        self.enclosing.as_ref()?.borrow().get_at(distance - 1, name)
+    }
+
+    pub fn assign_at(&mut self, distance: usize, name: &str, value: Object) -> bool {
+        if distance == 0 {
+            return assign_value(&mut self.values, name, value);
+        }
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow_mut().assign_at(distance - 1, name, value),
+            None => false,
+        }
+    }
+
+    /// Reads `name` from the outermost scope, where unresolved variables live.
+    pub fn get_global(&self, name: &str) -> Option<Object> {
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow().get_global(name),
+            None => self.values.get(name).cloned(),
+        }
+    }
+
+    pub fn assign_global(&mut self, name: &str, value: Object) -> bool {
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow_mut().assign_global(name, value),
+            None => assign_value(&mut self.values, name, value),
+        }
+    }
+}
+
+fn assign_value(values: &mut HashMap<String, Object>, name: &str, value: Object) -> bool {
+    match values.get_mut(name) {
+        Some(slot) => {
+            *slot = value;
+            true
+        }
+        None => false,
+    }
+}
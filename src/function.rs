@@ -0,0 +1,64 @@
+use std::{cell::RefCell, fmt::Debug, rc::Rc};
+
+use crate::ast::{eval_block, FunctionDecl, Object};
+use crate::environment::Environment;
+use crate::lox::RuntimeError;
+
+pub struct LoxFunction {
+    declaration: Rc<FunctionDecl>,
+    closure: Rc<RefCell<Environment>>,
+    is_initializer: bool,
+}
+
+impl LoxFunction {
+    pub fn new(
+        declaration: Rc<FunctionDecl>,
+        closure: Rc<RefCell<Environment>>,
+        is_initializer: bool,
+    ) -> LoxFunction {
+        LoxFunction {
+            declaration,
+            closure,
+            is_initializer,
+        }
+    }
+
+    pub fn arity(&self) -> usize {
+        self.declaration.params.len()
+    }
+
+    pub fn bind(&self, instance: Object) -> LoxFunction {
+        let env = Environment::new_enclosing(&self.closure);
+        env.borrow_mut().define("this".to_string(), instance);
+        LoxFunction::new(Rc::clone(&self.declaration), env, self.is_initializer)
+    }
+
+    /// Runs the body with `arguments` bound, `depth` calls deep.
+    pub fn call(&self, arguments: Vec<Object>, depth: usize) -> Result<Object, RuntimeError> {
+        let env = Environment::new_enclosing(&self.closure);
+        for (param, argument) in self.declaration.params.iter().zip(arguments) {
+            env.borrow_mut().define(param.lexeme.to_string(), argument);
+        }
+        let value = eval_block(&self.declaration.body, env, depth)?;
+        if self.is_initializer {
+            return Ok(self
+                .closure
+                .borrow()
+                .get_at(0, "this")
+                .unwrap_or(Object::Nil));
+        }
+        Ok(value.unwrap_or(Object::Nil))
+    }
+}
+
+impl PartialEq for LoxFunction {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl Debug for LoxFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn {}>", self.declaration.name.lexeme)
+    }
+}
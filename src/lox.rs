@@ -1,60 +1,225 @@
-use std::fmt::Display;
+use std::{cell::RefCell, fmt::Display, io, rc::Rc};
 
-#[derive(Clone)]
+use crate::reporter::{Collector, SharedReporter};
+use crate::token::{Span, Token, TokenType};
+
+/// Identifies which stage produced an error.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ErrorCode {
+    Scan,
+    Parse,
+    Resolve,
+    Runtime,
+}
+
+impl ErrorCode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::Scan => "E0001",
+            ErrorCode::Parse => "E0002",
+            ErrorCode::Resolve => "E0003",
+            ErrorCode::Runtime => "E0004",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Severity {
+    Error,
+}
+
+impl Severity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct Error {
-    line: usize,
-    place: String,
-    message: String,
+    pub code: ErrorCode,
+    pub severity: Severity,
+    pub span: Span,
+    /// Where in the source the error was found, e.g. "at 'x'".
+    pub place: String,
+    pub message: String,
+    /// Extra lines of context such as the call stack.
+    pub notes: Vec<String>,
 }
 
 impl Error {
-    pub fn new(line: usize, place: String, message: String) -> Error {
+    pub fn new(code: ErrorCode, span: Span, place: String, message: String) -> Error {
         Error {
-            line,
+            code,
+            severity: Severity::Error,
+            span,
             place,
             message,
+            notes: Vec::new(),
+        }
+    }
+}
+
+/// A function call that was active when a runtime error unwound through it.
+#[derive(Clone, Debug)]
+pub struct Frame {
+    pub function: String,
+    pub line: usize,
+    /// How many more identical calls directly enclosed this one, e.g. from
+    /// runaway recursion. They are folded into this frame.
+    pub repeats: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct RuntimeError {
+    /// The source the error points at, e.g. the offending operand.
+    pub span: Span,
+    pub message: String,
+    /// Active calls, innermost first.
+    pub frames: Vec<Frame>,
+}
+
+impl RuntimeError {
+    pub fn new(span: Span, message: String) -> RuntimeError {
+        RuntimeError {
+            span,
+            message,
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn with_frame(mut self, function: String, line: usize) -> RuntimeError {
+        match self.frames.last_mut() {
+            Some(last) if last.function == function && last.line == line => last.repeats += 1,
+            _ => self.frames.push(Frame {
+                function,
+                line,
+                repeats: 0,
+            }),
+        }
+        self
+    }
+
+    /// One line per frame, innermost first, noting how often it repeated.
+    pub fn trace(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        for frame in &self.frames {
+            lines.push(format!(
+                "in {} called from [line {}]",
+                frame.function, frame.line
+            ));
+            if frame.repeats > 0 {
+                lines.push(format!("... repeated {} more times", frame.repeats));
+            }
         }
+        lines
     }
 }
 
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}\n[line {}]", self.message, self.span.line)?;
+        self.trace()
+            .iter()
+            .try_for_each(|line| write!(f, "\n  {}", line))
+    }
+}
+
+/// Everything that can stop a program from running to completion. Stage
+/// errors have already been sent to the reporter by the time they get here.
+#[derive(Debug)]
+pub enum LoxError {
+    /// A script couldn't be read; holds the path.
+    Io(String, io::Error),
+    Scan(Vec<Error>),
+    Parse(Vec<Error>),
+    Resolve(Vec<Error>),
+    Runtime(RuntimeError),
+}
+
+impl Display for LoxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoxError::Io(path, error) => write!(f, "Could not read '{}': {}", path, error),
+            LoxError::Scan(errors) | LoxError::Parse(errors) | LoxError::Resolve(errors) => {
+                errors.iter().try_for_each(|error| writeln!(f, "{}", error))
+            }
+            LoxError::Runtime(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for LoxError {}
+
 #[derive(Clone)]
 pub struct Lox {
     pub had_error: bool,
     pub errors: Vec<Error>,
+    /// Shared by every clone, so all stages report to the same place.
+    reporter: SharedReporter,
 }
 
 impl Display for Lox {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        self.errors.iter().fold(Ok(()), |result, token| {
-            result.and_then(|_| writeln!(f, "{}", token))
-        })
+        self.errors
+            .iter()
+            .try_for_each(|error| writeln!(f, "{}", error))
     }
 }
 
 impl Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{} {} {}", self.line, self.place, self.message)
+        write!(f, "{} {} {}", self.span.line, self.place, self.message)
     }
 }
 
 impl Lox {
+    /// A collector that prints nothing, for tests and embedders.
+    #[allow(dead_code)]
     pub fn new() -> Lox {
+        Lox::with_reporter(Rc::new(RefCell::new(Collector::default())))
+    }
+
+    pub fn with_reporter(reporter: SharedReporter) -> Lox {
         Lox {
             had_error: false,
             errors: Vec::new(),
+            reporter,
         }
     }
-    pub fn error(&mut self, line: usize, message: String) {
-        self.report(line, "".to_string(), message)
+
+    pub fn error(&mut self, code: ErrorCode, span: Span, message: String) {
+        self.report(Error::new(code, span, "".to_string(), message))
+    }
+
+    pub fn error_at(&mut self, code: ErrorCode, token: &Token, message: String) {
+        // A string token's lexeme is its value, so the quotes are put back.
+        let place = match token.token_type {
+            TokenType::EOF => "at end".to_string(),
+            TokenType::String | TokenType::Interpolation => {
+                format!("at '\"{}\"'", token.lexeme)
+            }
+            _ => format!("at '{}'", token.lexeme),
+        };
+        self.report(Error::new(code, token.span, place, message))
     }
 
-    fn report(&mut self, line: usize, place: String, message: String) {
-        self.had_error = true;
-        let error = Error::new(line, place, message);
-        eprintln!(
-            "[line {}] Error {}: {}",
-            error.line, error.place, error.message
+    pub fn runtime_error(&mut self, error: &RuntimeError) {
+        let mut report = Error::new(
+            ErrorCode::Runtime,
+            error.span,
+            "".to_string(),
+            error.message.clone(),
         );
+        report.notes = error.trace();
+        self.reporter.borrow_mut().report(&report);
+        self.errors.push(report);
+    }
+
+    fn report(&mut self, error: Error) {
+        self.had_error = true;
+        self.reporter.borrow_mut().report(&error);
         self.errors.push(error);
     }
 }
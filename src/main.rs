@@ -1,89 +1,272 @@
+use std::cell::RefCell;
 use std::io::{self, Write};
+use std::rc::Rc;
 pub(crate) use std::{fs, process};
+use std::{panic, thread};
 
-use ast::{eval_stmt, Expr, Object, Stmt};
-use lox::Lox;
+use ast::{eval_stmt, Object, Stmt};
+use clox::compiler::Compiler;
+use clox::vm::{InterpretResult, VM};
+use diagnostic::line_span;
+use environment::Environment;
+use lox::{Lox, LoxError, RuntimeError};
 use parser::{Parse, Parser};
+use reporter::{HumanReporter, JsonReporter, SharedReporter};
+use resolver::{Resolve, Resolver};
 use scanner::{Scan, Scanner};
-use token::Tokens;
 
 mod ast;
+mod class;
 mod clox;
+mod diagnostic;
+mod environment;
+mod function;
 mod lox;
 mod parser;
+mod reporter;
+mod resolver;
 mod scanner;
 mod token;
+// The mutating visitors are for AST rewrites no pass performs yet.
+#[allow(dead_code)]
+mod visitor;
+
+/// How diagnostics are written to stderr.
+#[derive(Clone, Copy)]
+enum ErrorFormat {
+    Human,
+    Json,
+}
+
+/// Which interpreter runs the program.
+#[derive(Clone, Copy)]
+enum Backend {
+    TreeWalk,
+    Vm,
+}
+
+// Exit codes from BSD sysexits.h.
+const EX_USAGE: i32 = 64;
+const EX_DATAERR: i32 = 65;
+const EX_SOFTWARE: i32 = 70;
+const EX_IOERR: i32 = 74;
+
+/// Native stack for the interpreter thread, enough for `ast::MAX_CALL_DEPTH`
+/// nested Lox calls in a debug build.
+const STACK_SIZE: usize = 64 * 1024 * 1024;
 
 fn main() {
-    let args: Vec<_> = std::env::args().collect();
-    if args.len() > 2 {
-        println!("Usage: lox [script]");
-    } else if args.len() == 2 {
-        run_file(&args[1]);
+    let interpreter = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(lox_main)
+        .expect("failed to start the interpreter thread");
+    if let Err(payload) = interpreter.join() {
+        panic::resume_unwind(payload);
+    }
+}
+
+fn lox_main() {
+    let mut args: Vec<_> = std::env::args().skip(1).collect();
+    let format = if take_flag(&mut args, "--json") {
+        ErrorFormat::Json
+    } else {
+        ErrorFormat::Human
+    };
+    let backend = if take_flag(&mut args, "--vm") {
+        Backend::Vm
     } else {
-        stdin_interactive();
+        Backend::TreeWalk
+    };
+    if args.len() > 1 {
+        eprintln!("Usage: lox [--json] [--vm] [script]");
+        process::exit(EX_USAGE);
+    } else if args.len() == 1 {
+        if let Err(error) = run_file(&args[0], format, backend) {
+            if let LoxError::Io(..) = error {
+                eprintln!("{}", error);
+            }
+            process::exit(exit_code(&error));
+        }
+    } else {
+        stdin_interactive(format, backend);
+    }
+}
+
+/// Removes `flag` from `args`, returning whether it was there.
+fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    match args.iter().position(|arg| arg == flag) {
+        Some(index) => {
+            args.remove(index);
+            true
+        }
+        None => false,
+    }
+}
+
+fn exit_code(error: &LoxError) -> i32 {
+    match error {
+        LoxError::Io(..) => EX_IOERR,
+        LoxError::Scan(_) | LoxError::Parse(_) | LoxError::Resolve(_) => EX_DATAERR,
+        LoxError::Runtime(_) => EX_SOFTWARE,
     }
 }
 
-pub fn stdin_interactive() {
+fn stdin_interactive(format: ErrorFormat, backend: Backend) {
+    let env = Environment::new();
+    let mut vm = VM::stdout();
     loop {
         print!("> ");
         let _ = io::stdout().flush();
         let mut buffer = String::new();
-        let _ = io::stdin().read_line(&mut buffer);
-        if buffer == "exit\n" {
-            break;
-        }
-        if let Ok(_tokens) = run(buffer.clone()) {
-        } else {
-            println!("Error");
-            process::exit(69);
+        match io::stdin().read_line(&mut buffer) {
+            Ok(0) | Err(_) => break,
+            Ok(_) if buffer == "exit\n" => break,
+            Ok(_) => {}
         }
+        let reporter = reporter(format, "<stdin>", &buffer);
+        // Errors have already been reported; the REPL keeps going.
+        let _ = match backend {
+            Backend::TreeWalk => run(buffer, reporter, &env),
+            Backend::Vm => run_vm(buffer, reporter, &mut vm),
+        };
     }
 }
 
-pub fn run_file(file: &String) {
-    let contents: String = fs::read_to_string(file).unwrap();
-    if let Ok(tokens) = run(contents) {
-        println!("{}", tokens);
-    } else {
-        println!("Error");
-        process::exit(69);
+fn run_file(file: &String, format: ErrorFormat, backend: Backend) -> Result<(), LoxError> {
+    let contents =
+        fs::read_to_string(file).map_err(|error| LoxError::Io(file.to_string(), error))?;
+    let reporter = reporter(format, file, &contents);
+    match backend {
+        Backend::TreeWalk => run(contents, reporter, &Environment::new()),
+        Backend::Vm => run_vm(contents, reporter, &mut VM::stdout()),
     }
 }
 
-fn run(code: String) -> Result<Tokens, String> {
-    let scanner = Scanner::new(code, Lox::new());
-    let t = match run_with_scanner(scanner) {
-        Ok(tokens) => {
-            let mut parser = Parser::new(tokens.clone());
-            let stmts = parser.parse();
-            for stmt in stmts {
-                execute(stmt);
-            }
-            tokens
+fn reporter(format: ErrorFormat, file: &str, code: &str) -> SharedReporter {
+    match format {
+        ErrorFormat::Human => Rc::new(RefCell::new(HumanReporter::new(code))),
+        ErrorFormat::Json => Rc::new(RefCell::new(JsonReporter::stderr(file))),
+    }
+}
+
+fn run(
+    code: String,
+    reporter: SharedReporter,
+    env: &Rc<RefCell<Environment>>,
+) -> Result<(), LoxError> {
+    let lox = Lox::with_reporter(reporter);
+    // The parser pulls tokens from the scanner as it goes.
+    let mut parser = Parser::new(Scanner::new(code, lox.clone()), lox.clone());
+    let stmts = parser.parse();
+    let scan_errors = parser.tokens().get_errors();
+    if scan_errors.had_error {
+        return Err(LoxError::Scan(scan_errors.errors));
+    }
+    if parser.get_errors().had_error {
+        return Err(LoxError::Parse(parser.get_errors().errors));
+    }
+    let mut resolver = Resolver::new(lox.clone());
+    resolver.resolve(&stmts);
+    if resolver.get_errors().had_error {
+        return Err(LoxError::Resolve(resolver.get_errors().errors));
+    }
+    for stmt in stmts {
+        if let Err(error) = execute(stmt, env) {
+            let mut lox = lox;
+            lox.runtime_error(&error);
+            return Err(LoxError::Runtime(error));
         }
-        Err(string) => {
-            println!("{}", string);
-            process::exit(69);
+    }
+    Ok(())
+}
+
+/// Compiles `code` to bytecode and runs it on `vm`.
+fn run_vm<W: Write>(
+    code: String,
+    reporter: SharedReporter,
+    vm: &mut VM<W>,
+) -> Result<(), LoxError> {
+    let lox = Lox::with_reporter(reporter);
+    let mut compiler = Compiler::new(Scanner::new(code.clone(), lox.clone()), lox.clone());
+    let chunk = compiler.compile();
+    let scan_errors = compiler.tokens().get_errors();
+    if scan_errors.had_error {
+        return Err(LoxError::Scan(scan_errors.errors));
+    }
+    if compiler.get_errors().had_error {
+        return Err(LoxError::Parse(compiler.get_errors().errors));
+    }
+    match vm.interpret(&chunk) {
+        InterpretResult::Ok => Ok(()),
+        InterpretResult::RuntimeError => {
+            let vm_error = vm.error.take().expect("runtime error without details");
+            // Bytecode only knows lines, so the whole line is underlined.
+            let span = line_span(&code, vm_error.line);
+            let error = RuntimeError::new(span, vm_error.message);
+            let mut lox = lox;
+            lox.runtime_error(&error);
+            Err(LoxError::Runtime(error))
         }
-    };
-    Ok(t)
+    }
 }
 
-fn execute(stmt: Stmt) -> Result<Object, Expr> {
-    Ok(eval_stmt(&stmt)?)
+fn execute(stmt: Stmt, env: &Rc<RefCell<Environment>>) -> Result<Option<Object>, RuntimeError> {
+    eval_stmt(&stmt, env)
 }
 
-pub fn run_with_scanner<S: Scan>(mut scanner: S) -> Result<Tokens, String> {
-    let error = scanner.get_errors();
-    let tokens: Tokens = scanner.scan_tokens();
-    if error.had_error {
-        let mut error_string: String = "".to_string();
-        for e in error.errors.clone() {
-            error_string = format!("{}\n{}", error_string, e);
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_errors_propagate_with_exit_codes() {
+        let missing = run_file(
+            &"does/not/exist.lox".to_string(),
+            ErrorFormat::Human,
+            Backend::TreeWalk,
+        );
+        assert_eq!(missing.map_err(|error| exit_code(&error)), Err(EX_IOERR));
+
+        let env = Environment::new();
+        let silent = || -> SharedReporter { Rc::new(RefCell::new(reporter::Collector::default())) };
+        for (code, expected) in [
+            ("print @;", EX_DATAERR),
+            ("print (1;", EX_DATAERR),
+            ("return 1;", EX_DATAERR),
+            ("print -nil;", EX_SOFTWARE),
+        ] {
+            let error = run(code.to_string(), silent(), &env).err().unwrap();
+            assert_eq!(exit_code(&error), expected, "for {}", code);
+            let mut vm = VM::new(Vec::new());
+            let error = run_vm(code.to_string(), silent(), &mut vm).err().unwrap();
+            assert_eq!(exit_code(&error), expected, "for {} on the VM", code);
+        }
+    }
+
+    #[test]
+    fn test_invalid_character_is_the_only_error() {
+        let env = Environment::new();
+        let mut vm = VM::new(Vec::new());
+        for vm_backend in [false, true] {
+            let collector = Rc::new(RefCell::new(reporter::Collector::default()));
+            let code = "print 1 @ 2;".to_string();
+            if vm_backend {
+                assert!(run_vm(code, collector.clone(), &mut vm).is_err());
+            } else {
+                assert!(run(code, collector.clone(), &env).is_err());
+            }
+            let errors: Vec<String> = collector
+                .borrow()
+                .errors
+                .iter()
+                .map(|error| error.message.clone())
+                .collect();
+            assert_eq!(
+                errors,
+                vec!["Invalid character"],
+                "on the VM: {}",
+                vm_backend
+            );
         }
-        return Err(format!("{}", error));
     }
-    Ok(tokens)
 }
@@ -1,46 +1,78 @@
-use crate::ast::{Expr, Object, Stmt};
-use crate::token::{TokenType, Tokens};
+use std::mem::discriminant;
+use std::rc::Rc;
+
+use crate::ast::{Depth, Expr, FunctionDecl, Object, Stmt};
+use crate::lox::{ErrorCode, Lox};
+use crate::token::{Span, Symbol, TokenBuilder, TokenType};
 
 use super::token::Token;
 
 type Result<T> = std::result::Result<T, ParseError>;
 
+/// Lox caps parameter and argument lists so the bytecode VM can store the
+/// count in a single byte.
+const MAX_ARGUMENTS: usize = 255;
+
 pub trait Parse {
     fn parse(&mut self) -> Vec<Stmt>;
+    fn get_errors(&self) -> Lox;
 }
 
 struct ParseError {
-    pub token_type: TokenType,
+    pub token: Token,
     pub message: String,
 }
 
 impl ParseError {
-    pub fn new(token_type: TokenType, message: String) -> ParseError {
-        ParseError {
-            token_type,
-            message,
-        }
+    pub fn new(token: Token, message: String) -> ParseError {
+        ParseError { token, message }
     }
 }
 
-pub struct Parser {
-    tokens: Tokens,
-    current: usize,
+/// Pulls tokens from `tokens` as it needs them, looking at most one token
+/// ahead.
+pub struct Parser<I: Iterator<Item = Token>> {
+    tokens: I,
+    current: Token,
+    previous: Token,
+    errors: Lox,
 }
 
-impl Parser {
-    pub fn new(tokens: Tokens) -> Parser {
-        Parser { tokens, current: 0 }
+impl<I: Iterator<Item = Token>> Parser<I> {
+    pub fn new(tokens: impl IntoIterator<Item = Token, IntoIter = I>, errors: Lox) -> Parser<I> {
+        let mut tokens = tokens.into_iter();
+        let current = tokens.next().unwrap_or_else(|| eof(Span::default()));
+        Parser {
+            tokens,
+            current,
+            previous: TokenBuilder::new().build(),
+            errors,
+        }
     }
+
+    /// The token source, e.g. to collect the scanner's errors after parsing.
+    pub fn tokens(&self) -> &I {
+        &self.tokens
+    }
+}
+
+pub fn eof(span: Span) -> Token {
+    Token::new(TokenType::EOF, Symbol::EMPTY, span)
 }
 
-impl Parse for Parser {
+impl<I: Iterator<Item = Token>> Parse for Parser<I> {
     fn parse(&mut self) -> Vec<Stmt> {
         let mut stmts = Vec::new();
         while !at_eof(self.peek_type()) {
-            stmts.push(self.get_statement());
+            if let Some(stmt) = self.declaration() {
+                stmts.push(stmt);
+            }
         }
-        return stmts;
+        stmts
+    }
+
+    fn get_errors(&self) -> Lox {
+        self.errors.clone()
     }
 }
 
@@ -48,12 +80,156 @@ fn at_eof(token_type: &TokenType) -> bool {
     *token_type == TokenType::EOF
 }
 
-impl Parser {
-    fn get_statement(&mut self) -> Stmt {
+impl<I: Iterator<Item = Token>> Parser<I> {
+    /// Parses one declaration. On a syntax error the error is reported, the
+    /// parser skips ahead to the next statement boundary and `None` is
+    /// returned so parsing can continue.
+    fn declaration(&mut self) -> Option<Stmt> {
+        match self.try_declaration() {
+            Ok(stmt) => Some(stmt),
+            // The scanner has already reported its error tokens.
+            Err(error) if error.token.token_type == TokenType::Error => {
+                self.errors.had_error = true;
+                self.synchronize();
+                None
+            }
+            Err(error) => {
+                self.errors
+                    .error_at(ErrorCode::Parse, &error.token, error.message);
+                self.synchronize();
+                None
+            }
+        }
+    }
+
+    fn try_declaration(&mut self) -> Result<Stmt> {
+        if self.compare(vec![TokenType::Class]) {
+            return self.class_declaration();
+        }
+        if self.compare(vec![TokenType::Fn]) {
+            return Ok(Stmt::Function(self.function("function")?));
+        }
+        if self.compare(vec![TokenType::Var]) {
+            return self.var_declaration();
+        }
+        self.get_statement()
+    }
+
+    fn synchronize(&mut self) {
+        self.advance();
+        while !at_eof(self.peek_type()) {
+            if self.previous().token_type == TokenType::Semicolon {
+                return;
+            }
+            match self.peek_type() {
+                TokenType::Class
+                | TokenType::Fn
+                | TokenType::Var
+                | TokenType::For
+                | TokenType::If
+                | TokenType::While
+                | TokenType::Print
+                | TokenType::Return => return,
+                _ => {
+                    self.advance();
+                }
+            }
+        }
+    }
+
+    fn class_declaration(&mut self) -> Result<Stmt> {
+        let name = self.consume(TokenType::Identifier, "Expect class name.".to_string())?;
+        let mut superclass = None;
+        if self.compare(vec![TokenType::Less]) {
+            let super_name =
+                self.consume(TokenType::Identifier, "Expect superclass name.".to_string())?;
+            superclass = Some(Box::new(Expr::Variable(super_name, Depth::default())));
+        }
+        self.consume(
+            TokenType::LeftBrace,
+            "Expect '{' before class body.".to_string(),
+        )?;
+        let mut methods = Vec::new();
+        while !self.check(TokenType::RightBrace) && !at_eof(self.peek_type()) {
+            methods.push(self.function("method")?);
+        }
+        self.consume(
+            TokenType::RightBrace,
+            "Expect '}' after class body.".to_string(),
+        )?;
+        Ok(Stmt::Class(name, superclass, methods))
+    }
+
+    fn function(&mut self, kind: &str) -> Result<Rc<FunctionDecl>> {
+        let name = self.consume(TokenType::Identifier, format!("Expect {} name.", kind))?;
+        self.consume(
+            TokenType::LeftParen,
+            format!("Expect '(' after {} name.", kind),
+        )?;
+        let mut params = Vec::new();
+        if !self.check(TokenType::RightParen) {
+            loop {
+                if params.len() >= MAX_ARGUMENTS {
+                    let token = self.peek().clone();
+                    self.errors.error_at(
+                        ErrorCode::Parse,
+                        &token,
+                        format!("Can't have more than {} parameters.", MAX_ARGUMENTS),
+                    );
+                }
+                params.push(
+                    self.consume(TokenType::Identifier, "Expect parameter name.".to_string())?,
+                );
+                if !self.compare(vec![TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+        self.consume(
+            TokenType::RightParen,
+            "Expect ')' after parameters.".to_string(),
+        )?;
+        self.consume(
+            TokenType::LeftBrace,
+            format!("Expect '{{' before {} body.", kind),
+        )?;
+        let body = self.block()?;
+        Ok(Rc::new(FunctionDecl { name, params, body }))
+    }
+
+    fn var_declaration(&mut self) -> Result<Stmt> {
+        let name = self.consume(TokenType::Identifier, "Expect variable name.".to_string())?;
+        let mut initializer = None;
+        if self.compare(vec![TokenType::Equal]) {
+            initializer = Some(Box::new(self.expression()?));
+        }
+        self.consume(
+            TokenType::Semicolon,
+            "Expect ';' after variable declaration.".to_string(),
+        )?;
+        Ok(Stmt::Var(name, initializer))
+    }
+
+    fn get_statement(&mut self) -> Result<Stmt> {
         if self.compare(vec![TokenType::Print]) {
             return self.print_stmt();
         }
-        return self.expression_stmt();
+        if self.compare(vec![TokenType::LeftBrace]) {
+            return Ok(Stmt::Block(self.block()?));
+        }
+        if self.compare(vec![TokenType::If]) {
+            return self.if_stmt();
+        }
+        if self.compare(vec![TokenType::While]) {
+            return self.while_stmt();
+        }
+        if self.compare(vec![TokenType::For]) {
+            return self.for_stmt();
+        }
+        if self.compare(vec![TokenType::Return]) {
+            return self.return_stmt();
+        }
+        self.expression_stmt()
     }
 
     fn compare(&mut self, types: Vec<TokenType>) -> bool {
@@ -63,80 +239,213 @@ impl Parser {
                 return true;
             }
         }
-        return false;
+        false
     }
 
     fn check(&self, t: TokenType) -> bool {
-        if at_eof(&self.peek_type()) {
+        if at_eof(self.peek_type()) {
             return false;
         }
         match t {
-            TokenType::Number(_) if matches!(self.peek().token_type, TokenType::Number(_)) => {
-                return true
-            }
-            TokenType::Number(_) => return false,
-            TokenType::String(_) if matches!(self.peek().token_type, TokenType::String(_)) => {
-                return true
+            TokenType::Integer(_) | TokenType::Number(_) => {
+                discriminant(self.peek_type()) == discriminant(&t)
             }
-            TokenType::String(_) => return false,
-            token_type => return self.peek().token_type == token_type,
+            token_type => self.peek().token_type == token_type,
         }
     }
 
     fn peek_type(&self) -> &TokenType {
-        return &self.peek().token_type;
+        &self.peek().token_type
     }
 
     fn peek(&self) -> &Token {
-        return &self.tokens[self.current];
+        &self.current
     }
 
     fn advance(&mut self) -> Token {
         if !at_eof(&self.peek().token_type) {
-            self.current += 1;
+            let next = self.tokens.next().unwrap_or_else(|| eof(self.current.span));
+            self.previous = std::mem::replace(&mut self.current, next);
         }
-        return self.previous();
+        self.previous()
     }
 
     fn previous(&self) -> Token {
-        return self.tokens[self.current - 1].clone();
+        self.previous.clone()
+    }
+
+    fn return_stmt(&mut self) -> Result<Stmt> {
+        let keyword = self.previous();
+        let mut value = None;
+        if !self.check(TokenType::Semicolon) {
+            value = Some(Box::new(self.expression()?));
+        }
+        self.consume(
+            TokenType::Semicolon,
+            "Expect ';' after return value.".to_string(),
+        )?;
+        Ok(Stmt::Return(keyword, value))
+    }
+
+    fn if_stmt(&mut self) -> Result<Stmt> {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'if'.".to_string())?;
+        let condition = self.expression()?;
+        self.consume(
+            TokenType::RightParen,
+            "Expect ')' after if condition.".to_string(),
+        )?;
+        let then_branch = self.get_statement()?;
+        let mut else_branch = None;
+        if self.compare(vec![TokenType::Else]) {
+            else_branch = Some(Box::new(self.get_statement()?));
+        }
+        Ok(Stmt::If(
+            Box::new(condition),
+            Box::new(then_branch),
+            else_branch,
+        ))
+    }
+
+    fn while_stmt(&mut self) -> Result<Stmt> {
+        self.consume(
+            TokenType::LeftParen,
+            "Expect '(' after 'while'.".to_string(),
+        )?;
+        let condition = self.expression()?;
+        self.consume(
+            TokenType::RightParen,
+            "Expect ')' after condition.".to_string(),
+        )?;
+        let body = self.get_statement()?;
+        Ok(Stmt::While(Box::new(condition), Box::new(body)))
+    }
+
+    fn for_stmt(&mut self) -> Result<Stmt> {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'for'.".to_string())?;
+        let initializer = if self.compare(vec![TokenType::Semicolon]) {
+            None
+        } else if self.compare(vec![TokenType::Var]) {
+            Some(self.var_declaration()?)
+        } else {
+            Some(self.expression_stmt()?)
+        };
+
+        let mut condition = Expr::Literal(Object::Boolean(true), Span::default());
+        if !self.check(TokenType::Semicolon) {
+            condition = self.expression()?;
+        }
+        self.consume(
+            TokenType::Semicolon,
+            "Expect ';' after loop condition.".to_string(),
+        )?;
+
+        let mut increment = None;
+        if !self.check(TokenType::RightParen) {
+            increment = Some(self.expression()?);
+        }
+        self.consume(
+            TokenType::RightParen,
+            "Expect ')' after for clauses.".to_string(),
+        )?;
+
+        let mut body = self.get_statement()?;
+        if let Some(increment) = increment {
+            body = Stmt::Block(vec![body, Stmt::Expression(Box::new(increment))]);
+        }
+        body = Stmt::While(Box::new(condition), Box::new(body));
+        if let Some(initializer) = initializer {
+            body = Stmt::Block(vec![initializer, body]);
+        }
+        Ok(body)
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt>> {
+        let mut stmts = Vec::new();
+        while !self.check(TokenType::RightBrace) && !at_eof(self.peek_type()) {
+            if let Some(stmt) = self.declaration() {
+                stmts.push(stmt);
+            }
+        }
+        self.consume(TokenType::RightBrace, "Expect '}' after block.".to_string())?;
+        Ok(stmts)
     }
 
-    fn print_stmt(&mut self) -> Stmt {
-        let value = self.expression();
-        self.consume(TokenType::Semicolon, "Expect ';' after value.".to_string());
-        return Stmt::Print(Box::new(value));
+    fn print_stmt(&mut self) -> Result<Stmt> {
+        let value = self.expression()?;
+        self.consume(TokenType::Semicolon, "Expect ';' after value.".to_string())?;
+        Ok(Stmt::Print(Box::new(value)))
     }
 
     fn consume(&mut self, token_type: TokenType, message: String) -> Result<Token> {
-        if self.check(token_type.clone()) {
+        if self.check(token_type) {
             return Ok(self.advance());
         }
-        return Err(ParseError::new(token_type, message));
+        Err(ParseError::new(self.peek().clone(), message))
     }
 
-    fn expression_stmt(&mut self) -> Stmt {
-        let value = self.expression();
-        self.consume(TokenType::Semicolon, "Expect ';' after value.".to_string());
-        return Stmt::Expression(Box::new(value));
+    fn expression_stmt(&mut self) -> Result<Stmt> {
+        let value = self.expression()?;
+        self.consume(TokenType::Semicolon, "Expect ';' after value.".to_string())?;
+        Ok(Stmt::Expression(Box::new(value)))
     }
 
-    fn expression(&mut self) -> Expr {
-        return self.equality();
+    fn expression(&mut self) -> Result<Expr> {
+        self.assignment()
     }
 
-    fn equality(&mut self) -> Expr {
-        let mut expr = self.comparison();
+    fn assignment(&mut self) -> Result<Expr> {
+        let expr = self.logic_or()?;
+        if self.compare(vec![TokenType::Equal]) {
+            let equals = self.previous();
+            let value = self.assignment()?;
+            match expr {
+                Expr::Variable(name, _) => {
+                    return Ok(Expr::Assign(name, Box::new(value), Depth::default()))
+                }
+                Expr::Get(object, name) => return Ok(Expr::Set(object, name, Box::new(value))),
+                // Reported without unwinding: the parser is not confused.
+                _ => self.errors.error_at(
+                    ErrorCode::Parse,
+                    &equals,
+                    "Invalid assignment target.".to_string(),
+                ),
+            }
+        }
+        Ok(expr)
+    }
+
+    fn logic_or(&mut self) -> Result<Expr> {
+        let mut expr = self.logic_and()?;
+        while self.compare(vec![TokenType::Or]) {
+            let operator: Token = self.previous();
+            let right: Expr = self.logic_and()?;
+            expr = Expr::Logical(Box::new(expr), operator, Box::new(right));
+        }
+        Ok(expr)
+    }
+
+    fn logic_and(&mut self) -> Result<Expr> {
+        let mut expr = self.equality()?;
+        while self.compare(vec![TokenType::And]) {
+            let operator: Token = self.previous();
+            let right: Expr = self.equality()?;
+            expr = Expr::Logical(Box::new(expr), operator, Box::new(right));
+        }
+        Ok(expr)
+    }
+
+    fn equality(&mut self) -> Result<Expr> {
+        let mut expr = self.comparison()?;
         while self.compare(vec![TokenType::BangEqual, TokenType::EqualEqual]) {
             let operator: Token = self.previous();
-            let right: Expr = self.comparison();
+            let right: Expr = self.comparison()?;
             expr = Expr::Binary(Box::new(expr), operator, Box::new(right));
         }
-        return expr;
+        Ok(expr)
     }
 
-    fn comparison(&mut self) -> Expr {
-        let mut expr: Expr = self.term();
+    fn comparison(&mut self) -> Result<Expr> {
+        let mut expr: Expr = self.term()?;
         while self.compare(vec![
             TokenType::Greater,
             TokenType::GreaterEqual,
@@ -144,67 +453,260 @@ impl Parser {
             TokenType::LessEqual,
         ]) {
             let operator: Token = self.previous();
-            let right: Expr = self.term();
+            let right: Expr = self.term()?;
             expr = Expr::Binary(Box::new(expr), operator, Box::new(right));
         }
-        return expr;
+        Ok(expr)
     }
 
-    fn term(&mut self) -> Expr {
-        let mut expr: Expr = self.factor();
+    fn term(&mut self) -> Result<Expr> {
+        let mut expr: Expr = self.factor()?;
         while self.compare(vec![TokenType::Minus, TokenType::Plus]) {
             let operator: Token = self.previous();
-            let right: Expr = self.factor();
+            let right: Expr = self.factor()?;
             expr = Expr::Binary(Box::new(expr), operator, Box::new(right));
         }
-        return expr;
+        Ok(expr)
     }
 
-    fn factor(&mut self) -> Expr {
-        let mut expr: Expr = self.unary();
+    fn factor(&mut self) -> Result<Expr> {
+        let mut expr: Expr = self.unary()?;
         while self.compare(vec![TokenType::Slash, TokenType::Star]) {
             let operator: Token = self.previous();
-            let right: Expr = self.unary();
+            let right: Expr = self.unary()?;
             expr = Expr::Binary(Box::new(expr), operator, Box::new(right));
         }
-        return expr;
+        Ok(expr)
     }
 
-    fn unary(&mut self) -> Expr {
+    fn unary(&mut self) -> Result<Expr> {
         if self.compare(vec![TokenType::Bang, TokenType::Minus]) {
             let op: Token = self.previous();
-            let right: Expr = self.unary();
-            return Expr::Unary(op, Box::new(right));
+            let right: Expr = self.unary()?;
+            return Ok(Expr::Unary(op, Box::new(right)));
+        }
+        self.call()
+    }
+
+    fn call(&mut self) -> Result<Expr> {
+        let mut expr = self.primary()?;
+        loop {
+            if self.compare(vec![TokenType::LeftParen]) {
+                expr = self.finish_call(expr)?;
+            } else if self.compare(vec![TokenType::Dot]) {
+                let name = self.consume(
+                    TokenType::Identifier,
+                    "Expect property name after '.'.".to_string(),
+                )?;
+                expr = Expr::Get(Box::new(expr), name);
+            } else {
+                break;
+            }
         }
-        return self.primary();
+        Ok(expr)
     }
 
-    fn primary(&mut self) -> Expr {
+    fn finish_call(&mut self, callee: Expr) -> Result<Expr> {
+        let mut arguments = Vec::new();
+        if !self.check(TokenType::RightParen) {
+            loop {
+                if arguments.len() >= MAX_ARGUMENTS {
+                    let token = self.peek().clone();
+                    self.errors.error_at(
+                        ErrorCode::Parse,
+                        &token,
+                        format!("Can't have more than {} arguments.", MAX_ARGUMENTS),
+                    );
+                }
+                arguments.push(self.expression()?);
+                if !self.compare(vec![TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+        let paren = self.consume(
+            TokenType::RightParen,
+            "Expect ')' after arguments.".to_string(),
+        )?;
+        Ok(Expr::Call(Box::new(callee), paren, arguments))
+    }
+
+    fn primary(&mut self) -> Result<Expr> {
         if self.compare(vec![TokenType::False]) {
-            return Expr::Literal(Object::Boolean(false));
+            return Ok(Expr::Literal(Object::Boolean(false), self.previous().span));
         }
         if self.compare(vec![TokenType::True]) {
-            return Expr::Literal(Object::Boolean(true));
+            return Ok(Expr::Literal(Object::Boolean(true), self.previous().span));
         }
         if self.compare(vec![TokenType::Nil]) {
-            return Expr::Literal(Object::Nil);
+            return Ok(Expr::Literal(Object::Nil, self.previous().span));
         }
 
-        if self.compare(vec![TokenType::String("".to_string())]) {
-            return Expr::Literal(Object::String(self.previous().lexeme));
+        if self.compare(vec![TokenType::String]) {
+            let token = self.previous();
+            return Ok(Expr::Literal(
+                Object::String(token.lexeme.to_string()),
+                token.span,
+            ));
+        }
+
+        if self.compare(vec![TokenType::Interpolation]) {
+            return self.interpolation();
+        }
+
+        if self.compare(vec![TokenType::Integer(0)]) {
+            if let TokenType::Integer(value) = self.previous().token_type {
+                return Ok(Expr::Literal(Object::Integer(value), self.previous().span));
+            }
         }
 
         if self.compare(vec![TokenType::Number(0.0)]) {
-            return Expr::Literal(Object::Number(
-                self.previous().lexeme.parse::<f64>().unwrap(),
-            ));
+            if let TokenType::Number(value) = self.previous().token_type {
+                return Ok(Expr::Literal(Object::Number(value), self.previous().span));
+            }
         }
 
-        let expr: Expr = self.expression();
-        let _ = self.consume(
-            TokenType::RightParen,
-            "Expect ')' after expression.".to_string(),
+        if self.compare(vec![TokenType::Super]) {
+            self.consume(TokenType::Dot, "Expect '.' after 'super'.".to_string())?;
+            let method = self.consume(
+                TokenType::Identifier,
+                "Expect superclass method name.".to_string(),
+            )?;
+            return Ok(Expr::Super(method, Depth::default()));
+        }
+
+        if self.compare(vec![TokenType::This]) {
+            return Ok(Expr::This(self.previous(), Depth::default()));
+        }
+
+        if self.compare(vec![TokenType::Identifier]) {
+            return Ok(Expr::Variable(self.previous(), Depth::default()));
+        }
+
+        if self.compare(vec![TokenType::LeftParen]) {
+            let expr: Expr = self.expression()?;
+            self.consume(
+                TokenType::RightParen,
+                "Expect ')' after expression.".to_string(),
+            )?;
+            return Ok(Expr::Grouping(Box::new(expr)));
+        }
+
+        Err(ParseError::new(
+            self.peek().clone(),
+            "Expect expression.".to_string(),
+        ))
+    }
+
+    /// Lowers `"a ${x} b"` to `"a" + str(x) + " b"`.
+    fn interpolation(&mut self) -> Result<Expr> {
+        let mut segment = self.previous();
+        let mut expr = string_literal(&segment);
+        loop {
+            let value = self.expression()?;
+            let stringified = Expr::Stringify(Box::new(value));
+            expr = concat(expr, &segment, stringified);
+
+            segment = if self.compare(vec![TokenType::Interpolation]) {
+                self.previous()
+            } else {
+                self.consume(
+                    TokenType::String,
+                    "Expect '}' after interpolated expression.".to_string(),
+                )?
+            };
+            expr = concat(expr, &segment, string_literal(&segment));
+            if segment.token_type == TokenType::String {
+                return Ok(expr);
+            }
+        }
+    }
+}
+
+fn string_literal(segment: &Token) -> Expr {
+    Expr::Literal(Object::String(segment.lexeme.to_string()), segment.span)
+}
+
+fn concat(lhs: Expr, segment: &Token, rhs: Expr) -> Expr {
+    let lexeme = TokenType::Plus
+        .fixed_lexeme()
+        .expect("'+' has a fixed lexeme");
+    let plus = Token::new(TokenType::Plus, Symbol::fixed(lexeme), segment.span);
+    Expr::Binary(Box::new(lhs), plus, Box::new(rhs))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::scanner::Scanner;
+
+    fn parse(code: &str) -> (Vec<Stmt>, Vec<String>) {
+        let mut parser = Parser::new(Scanner::new(code.to_string(), Lox::new()), Lox::new());
+        let stmts = parser.parse();
+        let errors = parser
+            .get_errors()
+            .errors
+            .iter()
+            .map(|error| error.to_string())
+            .collect();
+        (stmts, errors)
+    }
+
+    #[test]
+    fn test_grouping() {
+        let (stmts, errors) = parse("(1 + 2) * 3;");
+        assert!(errors.is_empty());
+        let Stmt::Expression(expr) = &stmts[0] else {
+            panic!("expected expression statement");
+        };
+        assert_eq!(expr.to_string(), "(* (group (+ 1 2)) 3)");
+    }
+
+    #[test]
+    fn test_interpolation_lowers_to_stringify() {
+        let (stmts, errors) = parse("\"a ${x} b\";");
+        assert!(errors.is_empty());
+        let Stmt::Expression(expr) = &stmts[0] else {
+            panic!("expected expression statement");
+        };
+        assert_eq!(expr.to_string(), "(+ (+ a  (str x))  b)");
+    }
+
+    #[test]
+    fn test_reports_several_errors_and_synchronizes() {
+        let (stmts, errors) = parse("var = 1;\nprint (1 + 2;\nvar ok = 3;\nok = ;");
+        assert_eq!(
+            errors,
+            vec![
+                "1 at '=' Expect variable name.",
+                "2 at ';' Expect ')' after expression.",
+                "4 at ';' Expect expression.",
+            ]
         );
-        return Expr::Grouping(Box::new(expr));
+        assert_eq!(stmts.len(), 1);
+        assert!(matches!(&stmts[0], Stmt::Var(name, _) if name.lexeme.as_str() == "ok"));
+    }
+
+    #[test]
+    fn test_unexpected_eof_does_not_recurse() {
+        let (stmts, errors) = parse("(");
+        assert!(stmts.is_empty());
+        assert_eq!(errors, vec!["1 at end Expect expression."]);
+
+        let (_, errors) = parse("fn f() { print 1;");
+        assert_eq!(errors, vec!["1 at end Expect '}' after block."]);
+    }
+
+    #[test]
+    fn test_invalid_assignment_target_keeps_parsing() {
+        let (stmts, errors) = parse("1 = 2;\nprint 3;");
+        assert_eq!(errors, vec!["1 at '=' Invalid assignment target."]);
+        assert_eq!(stmts.len(), 2);
+    }
+
+    #[test]
+    fn test_error_at_string_quotes_its_value() {
+        let (_, errors) = parse("print 1 \"a\\tb\";");
+        assert_eq!(errors, vec!["1 at '\"a\tb\"' Expect ';' after value."]);
     }
 }
@@ -0,0 +1,182 @@
+use std::{
+    cell::RefCell,
+    io::{self, Write},
+    rc::Rc,
+};
+
+use crate::diagnostic::render;
+use crate::lox::{Error, ErrorCode};
+
+/// Receives every diagnostic a `Lox` collects, as it is reported.
+pub trait ErrorReporter {
+    fn report(&mut self, error: &Error);
+}
+
+pub type SharedReporter = Rc<RefCell<dyn ErrorReporter>>;
+
+/// Writes errors to stderr as text, quoting the offending source line.
+pub struct HumanReporter {
+    source: Rc<str>,
+}
+
+impl HumanReporter {
+    pub fn new(source: &str) -> HumanReporter {
+        HumanReporter {
+            source: source.into(),
+        }
+    }
+}
+
+impl ErrorReporter for HumanReporter {
+    fn report(&mut self, error: &Error) {
+        let span = error.span;
+        let mut text = if error.code == ErrorCode::Runtime {
+            format!("{}\n", error.message)
+        } else {
+            format!(
+                "[line {}:{}] Error{}: {}\n",
+                span.line,
+                span.column,
+                if error.place.is_empty() {
+                    String::new()
+                } else {
+                    format!(" {}", error.place)
+                },
+                error.message
+            )
+        };
+        text.push_str(&render(&self.source, span));
+        if error.code == ErrorCode::Runtime {
+            text.push_str(&format!("[line {}]\n", span.line));
+        }
+        error
+            .notes
+            .iter()
+            .for_each(|note| text.push_str(&format!("  {}\n", note)));
+        eprint!("{}", text);
+    }
+}
+
+/// Keeps errors in memory without printing anything, for tests and
+/// embedders.
+#[derive(Default)]
+pub struct Collector {
+    pub errors: Vec<Error>,
+}
+
+impl ErrorReporter for Collector {
+    fn report(&mut self, error: &Error) {
+        self.errors.push(error.clone());
+    }
+}
+
+/// Writes one JSON object per error, for editors and CI.
+pub struct JsonReporter<W: Write> {
+    file: String,
+    out: W,
+}
+
+impl JsonReporter<io::Stderr> {
+    pub fn stderr(file: &str) -> JsonReporter<io::Stderr> {
+        JsonReporter::new(file, io::stderr())
+    }
+}
+
+impl<W: Write> JsonReporter<W> {
+    pub fn new(file: &str, out: W) -> JsonReporter<W> {
+        JsonReporter {
+            file: file.to_string(),
+            out,
+        }
+    }
+}
+
+impl<W: Write> ErrorReporter for JsonReporter<W> {
+    fn report(&mut self, error: &Error) {
+        let span = error.span;
+        let notes: Vec<String> = error.notes.iter().map(|note| json_string(note)).collect();
+        // A reporter has nowhere to report its own failures.
+        let _ = writeln!(
+            self.out,
+            "{{\"code\":{},\"severity\":{},\"file\":{},\"span\":{{\"start\":{},\"end\":{},\"line\":{},\"column\":{}}},\"message\":{},\"notes\":[{}]}}",
+            json_string(error.code.as_str()),
+            json_string(error.severity.as_str()),
+            json_string(&self.file),
+            span.start,
+            span.end,
+            span.line,
+            span.column,
+            json_string(&error.message),
+            notes.join(",")
+        );
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::lox::Lox;
+    use crate::token::Span;
+
+    #[test]
+    fn test_json_lines() {
+        let out = Rc::new(RefCell::new(Vec::new()));
+        struct Shared(Rc<RefCell<Vec<u8>>>);
+        impl Write for Shared {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.borrow_mut().write(buf)
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+        let reporter = JsonReporter::new("main.lox", Shared(Rc::clone(&out)));
+        let mut lox = Lox::with_reporter(Rc::new(RefCell::new(reporter)));
+        let span = Span {
+            start: 4,
+            end: 5,
+            line: 1,
+            column: 5,
+        };
+        lox.error(ErrorCode::Scan, span, "Invalid \"character\"".to_string());
+
+        let line = String::from_utf8(out.borrow().clone()).unwrap();
+        assert_eq!(
+            line,
+            "{\"code\":\"E0001\",\"severity\":\"error\",\"file\":\"main.lox\",\
+             \"span\":{\"start\":4,\"end\":5,\"line\":1,\"column\":5},\
+             \"message\":\"Invalid \\\"character\\\"\",\"notes\":[]}\n"
+        );
+    }
+
+    #[test]
+    fn test_collector_shared_between_clones() {
+        let collector = Rc::new(RefCell::new(Collector::default()));
+        let lox = Lox::with_reporter(collector.clone());
+        let mut scan = lox.clone();
+        let mut parse = lox.clone();
+        scan.error(ErrorCode::Scan, Span::default(), "a".to_string());
+        parse.error(ErrorCode::Parse, Span::default(), "b".to_string());
+
+        let codes: Vec<ErrorCode> = collector.borrow().errors.iter().map(|e| e.code).collect();
+        assert_eq!(codes, vec![ErrorCode::Scan, ErrorCode::Parse]);
+    }
+}
@@ -0,0 +1,385 @@
+use std::{collections::HashMap, rc::Rc};
+
+use crate::ast::{Depth, Expr, FunctionDecl, Object, Stmt};
+use crate::lox::{ErrorCode, Lox};
+use crate::token::{Span, Token};
+use crate::visitor::{walk_expr, walk_stmt, ExprVisitor, StmtVisitor};
+
+pub trait Resolve {
+    fn resolve(&mut self, stmts: &[Stmt]);
+    fn get_errors(&self) -> Lox;
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionType {
+    None,
+    Function,
+    Initializer,
+    Method,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+/// Walks the AST once before execution, recording how many scopes up each
+/// local variable reference resolves and reporting static errors.
+pub struct Resolver {
+    /// Each scope maps a name to whether its initializer has finished.
+    scopes: Vec<HashMap<String, bool>>,
+    current_function: FunctionType,
+    current_class: ClassType,
+    errors: Lox,
+}
+
+impl Resolve for Resolver {
+    fn resolve(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            self.resolve_stmt(stmt);
+        }
+    }
+
+    fn get_errors(&self) -> Lox {
+        self.errors.clone()
+    }
+}
+
+impl Resolver {
+    pub fn new(errors: Lox) -> Resolver {
+        Resolver {
+            scopes: Vec::new(),
+            current_function: FunctionType::None,
+            current_class: ClassType::None,
+            errors,
+        }
+    }
+
+    fn resolve_stmt(&mut self, stmt: &Stmt) {
+        walk_stmt(self, stmt)
+    }
+
+    fn resolve_expr(&mut self, expr: &Expr) {
+        walk_expr(self, expr)
+    }
+
+    fn resolve_class(
+        &mut self,
+        name: &Token,
+        superclass: Option<&Expr>,
+        methods: &[Rc<FunctionDecl>],
+    ) {
+        let enclosing_class = self.current_class;
+        self.current_class = ClassType::Class;
+        self.declare(name);
+        self.define(name);
+
+        if let Some(superclass) = superclass {
+            if let Expr::Variable(super_name, _) = superclass {
+                if super_name.lexeme == name.lexeme {
+                    self.error(super_name, "A class can't inherit from itself.");
+                }
+            }
+            self.current_class = ClassType::Subclass;
+            self.resolve_expr(superclass);
+            self.begin_scope();
+            self.define_name("super");
+        }
+
+        self.begin_scope();
+        self.define_name("this");
+        for method in methods {
+            let function_type = if method.name.lexeme.as_str() == "init" {
+                FunctionType::Initializer
+            } else {
+                FunctionType::Method
+            };
+            self.resolve_function(method, function_type);
+        }
+        self.end_scope();
+
+        if superclass.is_some() {
+            self.end_scope();
+        }
+        self.current_class = enclosing_class;
+    }
+
+    fn resolve_function(&mut self, declaration: &FunctionDecl, function_type: FunctionType) {
+        let enclosing_function = self.current_function;
+        self.current_function = function_type;
+        self.begin_scope();
+        for param in &declaration.params {
+            self.declare(param);
+            self.define(param);
+        }
+        self.resolve(&declaration.body);
+        self.end_scope();
+        self.current_function = enclosing_function;
+    }
+
+    fn resolve_local(&mut self, name: &Token, depth: &Depth) {
+        self.resolve_name(name.lexeme.as_str(), depth);
+    }
+
+    fn resolve_name(&mut self, name: &str, depth: &Depth) {
+        let found = self
+            .scopes
+            .iter()
+            .rev()
+            .position(|scope| scope.contains_key(name));
+        depth.set(found);
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &Token) {
+        let Some(scope) = self.scopes.last_mut() else {
+            return;
+        };
+        if scope.contains_key(name.lexeme.as_str()) {
+            self.error(name, "Already a variable with this name in this scope.");
+            return;
+        }
+        scope.insert(name.lexeme.to_string(), false);
+    }
+
+    fn define(&mut self, name: &Token) {
+        self.define_name(name.lexeme.as_str());
+    }
+
+    fn define_name(&mut self, name: &str) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), true);
+        }
+    }
+
+    fn error(&mut self, token: &Token, message: &str) {
+        self.errors
+            .error_at(ErrorCode::Resolve, token, message.to_string());
+    }
+}
+
+impl StmtVisitor<()> for Resolver {
+    fn visit_block(&mut self, stmts: &[Stmt]) {
+        self.begin_scope();
+        self.resolve(stmts);
+        self.end_scope();
+    }
+
+    fn visit_class(
+        &mut self,
+        name: &Token,
+        superclass: Option<&Expr>,
+        methods: &[Rc<FunctionDecl>],
+    ) {
+        self.resolve_class(name, superclass, methods)
+    }
+
+    fn visit_expression(&mut self, expr: &Expr) {
+        self.resolve_expr(expr);
+    }
+
+    fn visit_function(&mut self, declaration: &Rc<FunctionDecl>) {
+        self.declare(&declaration.name);
+        self.define(&declaration.name);
+        self.resolve_function(declaration, FunctionType::Function);
+    }
+
+    fn visit_if(&mut self, condition: &Expr, then_branch: &Stmt, else_branch: Option<&Stmt>) {
+        self.resolve_expr(condition);
+        self.resolve_stmt(then_branch);
+        if let Some(else_branch) = else_branch {
+            self.resolve_stmt(else_branch);
+        }
+    }
+
+    fn visit_print(&mut self, expr: &Expr) {
+        self.resolve_expr(expr);
+    }
+
+    fn visit_return(&mut self, keyword: &Token, value: Option<&Expr>) {
+        if self.current_function == FunctionType::None {
+            self.error(keyword, "Can't return from top-level code.");
+        }
+        if let Some(value) = value {
+            if self.current_function == FunctionType::Initializer {
+                self.error(keyword, "Can't return a value from an initializer.");
+            }
+            self.resolve_expr(value);
+        }
+    }
+
+    fn visit_var(&mut self, name: &Token, initializer: Option<&Expr>) {
+        self.declare(name);
+        if let Some(initializer) = initializer {
+            self.resolve_expr(initializer);
+        }
+        self.define(name);
+    }
+
+    fn visit_while(&mut self, condition: &Expr, body: &Stmt) {
+        self.resolve_expr(condition);
+        self.resolve_stmt(body);
+    }
+}
+
+impl ExprVisitor<()> for Resolver {
+    fn visit_assign(&mut self, name: &Token, value: &Expr, depth: &Depth) {
+        self.resolve_expr(value);
+        self.resolve_local(name, depth);
+    }
+
+    fn visit_binary(&mut self, lhs: &Expr, _op: &Token, rhs: &Expr) {
+        self.resolve_expr(lhs);
+        self.resolve_expr(rhs);
+    }
+
+    fn visit_call(&mut self, callee: &Expr, _paren: &Token, arguments: &[Expr]) {
+        self.resolve_expr(callee);
+        for argument in arguments {
+            self.resolve_expr(argument);
+        }
+    }
+
+    fn visit_get(&mut self, object: &Expr, _name: &Token) {
+        self.resolve_expr(object);
+    }
+
+    fn visit_grouping(&mut self, expr: &Expr) {
+        self.resolve_expr(expr);
+    }
+
+    fn visit_literal(&mut self, _value: &Object, _span: Span) {}
+
+    fn visit_logical(&mut self, lhs: &Expr, _op: &Token, rhs: &Expr) {
+        self.resolve_expr(lhs);
+        self.resolve_expr(rhs);
+    }
+
+    fn visit_set(&mut self, object: &Expr, _name: &Token, value: &Expr) {
+        self.resolve_expr(value);
+        self.resolve_expr(object);
+    }
+
+    fn visit_stringify(&mut self, expr: &Expr) {
+        self.resolve_expr(expr);
+    }
+
+    fn visit_super(&mut self, keyword: &Token, depth: &Depth) {
+        match self.current_class {
+            ClassType::None => self.error(keyword, "Can't use 'super' outside of a class."),
+            ClassType::Class => {
+                self.error(keyword, "Can't use 'super' in a class with no superclass.")
+            }
+            ClassType::Subclass => {}
+        }
+        self.resolve_name("super", depth);
+    }
+
+    fn visit_this(&mut self, keyword: &Token, depth: &Depth) {
+        if self.current_class == ClassType::None {
+            self.error(keyword, "Can't use 'this' outside of a class.");
+            return;
+        }
+        self.resolve_name("this", depth);
+    }
+
+    fn visit_unary(&mut self, _op: &Token, rhs: &Expr) {
+        self.resolve_expr(rhs);
+    }
+
+    fn visit_variable(&mut self, name: &Token, depth: &Depth) {
+        if let Some(false) = self
+            .scopes
+            .last()
+            .and_then(|scope| scope.get(name.lexeme.as_str()))
+        {
+            self.error(name, "Can't read local variable in its own initializer.");
+        }
+        self.resolve_local(name, depth);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::{Parse, Parser};
+    use crate::scanner::Scanner;
+
+    fn resolve(code: &str) -> Lox {
+        let tokens = Scanner::new(code.to_string(), Lox::new());
+        let stmts = Parser::new(tokens, Lox::new()).parse();
+        let mut resolver = Resolver::new(Lox::new());
+        resolver.resolve(&stmts);
+        resolver.get_errors()
+    }
+
+    #[test]
+    fn test_valid_program_has_no_errors() {
+        let errors = resolve(
+            "var a = 1; { var b = a; } fn f(x) { { var y = x; return y; } }
+             class A { init() { this.x = 1; return; } } class B < A { m() { return super.init(); } }",
+        );
+        assert!(!errors.had_error);
+    }
+
+    #[test]
+    fn test_static_errors() {
+        for code in [
+            "{ var a = a; }",
+            "{ var a = 1; var a = 2; }",
+            "fn f(a, a) {}",
+            "return 1;",
+            "print this;",
+            "fn f() { return this; }",
+            "class A { init() { return 1; } }",
+            "class A < A {}",
+            "class A { m() { return super.m(); } }",
+            "super.m();",
+        ] {
+            let errors = resolve(code);
+            assert!(errors.had_error, "expected a resolve error for {}", code);
+            assert_eq!(errors.errors.len(), 1, "expected one error for {}", code);
+        }
+    }
+
+    #[test]
+    fn test_records_scope_depths() {
+        let tokens = Scanner::new(
+            "var g; { var a; { fn f() { return a + g; } } }".to_string(),
+            Lox::new(),
+        );
+        let stmts = Parser::new(tokens, Lox::new()).parse();
+        Resolver::new(Lox::new()).resolve(&stmts);
+
+        let Stmt::Block(outer) = &stmts[1] else {
+            panic!("expected block");
+        };
+        let Stmt::Block(inner) = &outer[1] else {
+            panic!("expected block");
+        };
+        let Stmt::Function(f) = &inner[0] else {
+            panic!("expected function");
+        };
+        let Stmt::Return(_, Some(value)) = &f.body[0] else {
+            panic!("expected return");
+        };
+        let Expr::Binary(a, _, g) = value.as_ref() else {
+            panic!("expected binary");
+        };
+        let (Expr::Variable(_, a_depth), Expr::Variable(_, g_depth)) = (a.as_ref(), g.as_ref())
+        else {
+            panic!("expected variables");
+        };
+        assert_eq!(a_depth.get(), Some(2));
+        assert_eq!(g_depth.get(), None);
+    }
+}
@@ -1,81 +1,126 @@
 use std::str::FromStr;
 
-use crate::{lox::Lox, token::TokenBuilder};
+use crate::{
+    lox::{ErrorCode, Lox},
+    token::{Interner, Span, Symbol, TokenBuilder},
+};
 
-use super::token::{Token, TokenType, Tokens};
+use super::token::{Token, TokenType};
 
 pub trait Scan {
-    fn scan_tokens(&mut self) -> Tokens;
     fn get_errors(&self) -> Lox;
 }
 
+/// Produces tokens lazily; iteration ends after the `EOF` token.
 pub struct Scanner {
-    tokens: Tokens,
     code: String,
     line: usize,
+    /// Column of `current`, counted in characters.
+    column: usize,
+    /// Byte offsets into `code`; always on character boundaries.
     start: usize,
+    start_line: usize,
+    start_column: usize,
     current: usize,
+    /// One entry per `${` still open, counting the `{`s nested inside it.
+    interpolations: Vec<usize>,
+    emitted_eof: bool,
+    symbols: Interner,
     errors: Lox,
 }
 
-impl Scan for Scanner {
-    fn scan_tokens(&mut self) -> Tokens {
+impl Iterator for Scanner {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
         while !self.is_finished() {
             self.start = self.current;
-            self.scan_token();
+            self.start_line = self.line;
+            self.start_column = self.column;
+            if let Some(token) = self.scan_token() {
+                return Some(token);
+            }
         }
-
-        let token = create_token(TokenType::EOF, "".to_string(), self.line);
-        self.tokens.push(token);
-        return self.tokens.clone();
+        if self.emitted_eof {
+            return None;
+        }
+        self.emitted_eof = true;
+        let span = Span {
+            start: self.current,
+            end: self.current,
+            line: self.line,
+            column: self.column,
+        };
+        Some(create_token(TokenType::EOF, Symbol::EMPTY, span))
     }
+}
 
+impl Scan for Scanner {
     fn get_errors(&self) -> Lox {
-        return self.errors.clone();
+        self.errors.clone()
     }
 }
 
-fn create_token(token_type: TokenType, lexeme: String, line: usize) -> Token {
-    // let text = self.code[self.start..self.current].to_string();
+fn create_token(token_type: TokenType, lexeme: Symbol, span: Span) -> Token {
     TokenBuilder::new()
         .token_type(token_type)
         .lexeme(lexeme)
-        .line(line)
+        .span(span)
         .build()
 }
 
 impl Scanner {
     pub fn new(code: String, errors: Lox) -> Scanner {
         Scanner {
-            tokens: Tokens::new(),
             code,
             line: 1,
+            column: 1,
             start: 0,
+            start_line: 1,
+            start_column: 1,
             current: 0,
+            interpolations: Vec::new(),
+            emitted_eof: false,
+            symbols: Interner::new(),
             errors,
         }
     }
 
-    fn scan_token(&mut self) {
+    /// Scans the next lexeme, returning `None` for whitespace and comments.
+    fn scan_token(&mut self) -> Option<Token> {
         let c = self.advance();
         if c.is_ascii_whitespace() {
             self.handle_new_line(c);
-            return;
+            return None;
         }
         if self.is_comment_line(c) {
             self.handle_comment_line();
-            return;
+            return None;
         } else if self.is_block_comment(c) {
             self.handle_block_comment();
-            return;
+            return None;
         }
-        let token = self.create_token_from_char(c);
-        self.tokens.push(token);
+        Some(self.create_token_from_char(c))
     }
 
     fn handle_new_line(&mut self, c: char) {
         if is_new_line(c) {
-            self.line += 1;
+            self.new_line();
+        }
+    }
+
+    /// Call once the `\n` has been consumed.
+    fn new_line(&mut self) {
+        self.line += 1;
+        self.column = 1;
+    }
+
+    fn span(&self) -> Span {
+        Span {
+            start: self.start,
+            end: self.current,
+            line: self.start_line,
+            column: self.start_column,
         }
     }
 }
@@ -90,12 +135,11 @@ impl Scanner {
     }
 
     fn match_char(&mut self, expected: char) -> bool {
-        let curr = self.code.as_bytes()[self.current] as char;
-        if self.is_finished() || curr != expected {
+        if self.is_finished() || self.peek() != expected {
             return false;
         }
-        self.current += 1;
-        return true;
+        self.advance();
+        true
     }
 
     fn is_finished(&self) -> bool {
@@ -103,44 +147,72 @@ impl Scanner {
     }
 
     fn handle_comment_line(&mut self) {
-        self.current += 1;
         while self.peek() != '\n' && !self.is_finished() {
             self.advance();
         }
     }
 
     fn peek(&self) -> char {
-        if self.is_finished() {
-            return '\0';
-        }
-        return self.code.as_bytes()[self.current] as char;
+        self.code[self.current..].chars().next().unwrap_or('\0')
     }
 
+    /// Consumes one character, or returns `'\0'` at the end of the source.
     fn advance(&mut self) -> char {
-        let res = self.code.as_bytes()[self.current] as char;
-        self.current += 1;
-        return res;
+        let Some(c) = self.code[self.current..].chars().next() else {
+            return '\0';
+        };
+        self.current += c.len_utf8();
+        self.column += 1;
+        c
     }
 
     fn is_block_comment(&mut self, c: char) -> bool {
         c == '/' && self.match_char('*')
     }
 
+    /// Skips a `/* */` comment, which may nest. Called after the opening `/*`.
     fn handle_block_comment(&mut self) {
-        self.current += 1;
-        while self.peek() != '*' && !self.match_char('/') && !self.is_finished() {
-            self.advance();
+        let mut depth = 1;
+        while depth > 0 {
+            if self.is_finished() {
+                self.errors.error(
+                    ErrorCode::Scan,
+                    self.span(),
+                    "Unterminated block comment.".to_string(),
+                );
+                return;
+            }
+            match self.advance() {
+                '/' if self.match_char('*') => depth += 1,
+                '*' if self.match_char('/') => depth -= 1,
+                '\n' => self.new_line(),
+                _ => {}
+            }
         }
-        self.advance();
-        self.advance();
     }
 
     fn create_token_from_char(&mut self, c: char) -> Token {
         match c {
             '(' => self.create_token(TokenType::LeftParen),
             ')' => self.create_token(TokenType::RightParen),
-            '{' => self.create_token(TokenType::LeftBrace),
-            '}' => self.create_token(TokenType::RightBrace),
+            '{' => {
+                if let Some(depth) = self.interpolations.last_mut() {
+                    *depth += 1;
+                }
+                self.create_token(TokenType::LeftBrace)
+            }
+            '}' => match self.interpolations.last_mut() {
+                // Closes a `${`, so the rest of the string literal follows.
+                Some(0) => {
+                    self.interpolations.pop();
+                    self.string()
+                }
+                Some(depth) => {
+                    *depth -= 1;
+                    self.create_token(TokenType::RightBrace)
+                }
+                None => self.create_token(TokenType::RightBrace),
+            },
             ',' => self.create_token(TokenType::Comma),
             '.' => self.create_token(TokenType::Dot),
             '-' => self.create_token(TokenType::Minus),
@@ -159,17 +231,23 @@ impl Scanner {
                 } else if is_name_char(c) {
                     self.identifier()
                 } else {
-                    self.errors
-                        .error(self.line, "Invalid character".to_string());
+                    self.errors.error(
+                        ErrorCode::Scan,
+                        self.span(),
+                        "Invalid character".to_string(),
+                    );
                     self.create_token(TokenType::Error)
                 }
             }
         }
     }
 
-    fn create_token(&self, token_type: TokenType) -> Token {
-        let text = self.code[self.start..self.current].to_string();
-        create_token(token_type, text, self.line)
+    fn create_token(&mut self, token_type: TokenType) -> Token {
+        let lexeme = match token_type.fixed_lexeme() {
+            Some(text) => Symbol::fixed(text),
+            None => self.symbols.intern(&self.code[self.start..self.current]),
+        };
+        create_token(token_type, lexeme, self.span())
     }
 
     fn handle_greater_than(&mut self) -> Token {
@@ -204,57 +282,502 @@ impl Scanner {
     }
 }
 
+/// Identifiers may use letters from any script.
 fn is_name_char(c: char) -> bool {
-    c.is_ascii_alphabetic() || c == '_'
+    c.is_alphabetic() || c == '_'
 }
 
 impl Scanner {
     fn identifier(&mut self) -> Token {
-        while self.peek().is_ascii_alphabetic() {
+        while is_name_char(self.peek()) || self.peek().is_numeric() {
             self.advance();
         }
         let word: &str = &self.code[self.start..self.current];
-        if let Ok(token_type) = TokenType::from_str(word) {
-            self.create_token(token_type)
-        } else {
-            println!(
-                "This should never happen, error creating token type from string value {}",
-                word
-            );
-            self.create_token(TokenType::Error)
-        }
+        let token_type = TokenType::from_str(word).unwrap_or(TokenType::Identifier);
+        self.create_token(token_type)
     }
 
+    /// Scans decimal, `0x` hex and `0b` binary literals. Two digits may be
+    /// separated by a single `_`. Literals with a fraction or exponent are
+    /// floats; everything else is an exact integer.
     fn number(&mut self) -> Token {
-        while self.peek().is_ascii_digit() {
-            self.advance();
+        if self.code[self.start..self.current] == *"0" {
+            let radix = match self.peek() {
+                'x' | 'X' => Some(16),
+                'b' | 'B' => Some(2),
+                _ => None,
+            };
+            if let Some(radix) = radix {
+                self.advance();
+                let digits_start = self.current;
+                if !self.digits(|c| c.is_digit(radix)) {
+                    return self.number_error("Invalid number.");
+                }
+                let digits = self.code[digits_start..self.current].to_string();
+                return self.integer(&digits, radix);
+            }
         }
+
+        let mut valid = self.digits(|c| c.is_ascii_digit());
+        let mut is_float = false;
         if self.peek() == '.' && self.peek_next().is_ascii_digit() {
             self.advance();
-            while self.peek().is_ascii_digit() {
+            valid &= self.digits(|c| c.is_ascii_digit());
+            is_float = true;
+        }
+        if matches!(self.peek(), 'e' | 'E') {
+            let sign = matches!(self.peek_next(), '+' | '-');
+            let digit = self.code[self.current..]
+                .chars()
+                .nth(if sign { 2 } else { 1 });
+            if digit.is_some_and(|c| c.is_ascii_digit()) {
                 self.advance();
+                if sign {
+                    self.advance();
+                }
+                valid &= self.digits(|c| c.is_ascii_digit());
+                is_float = true;
             }
         }
-        let num: f64 = self.code[self.start..self.current].parse().unwrap();
-        return self.create_token(TokenType::Number(num));
+
+        if !valid {
+            return self.number_error("Invalid number.");
+        }
+        let text = self.code[self.start..self.current].to_string();
+        if !is_float {
+            return self.integer(&text, 10);
+        }
+        match text.replace('_', "").parse() {
+            Ok(num) => self.create_token(TokenType::Number(num)),
+            Err(_) => self.number_error("Invalid number."),
+        }
     }
 
-    fn peek_next(&self) -> char {
-        if self.current + 1 >= self.code.len() {
-            return '\0';
+    /// Consumes a run of digits and `_` separators, returning whether every
+    /// `_` sits between two digits.
+    fn digits(&mut self, is_digit: impl Fn(char) -> bool) -> bool {
+        let mut valid = true;
+        loop {
+            let c = self.peek();
+            if c == '_' {
+                let previous = self.code[..self.current].chars().next_back();
+                valid &= previous.is_some_and(&is_digit) && is_digit(self.peek_next());
+            } else if !is_digit(c) {
+                return valid;
+            }
+            self.advance();
         }
-        return self.code.as_bytes()[self.current + 1] as char;
     }
 
+    fn integer(&mut self, digits: &str, radix: u32) -> Token {
+        if digits.is_empty() {
+            return self.number_error("Invalid number.");
+        }
+        match i64::from_str_radix(&digits.replace('_', ""), radix) {
+            Ok(value) => self.create_token(TokenType::Integer(value)),
+            Err(_) => self.number_error("Integer literal is too large."),
+        }
+    }
+
+    fn number_error(&mut self, message: &str) -> Token {
+        self.errors
+            .error(ErrorCode::Scan, self.span(), message.to_string());
+        self.create_token(TokenType::Error)
+    }
+
+    fn peek_next(&self) -> char {
+        self.code[self.current..].chars().nth(1).unwrap_or('\0')
+    }
+
+    /// Scans a string literal, or the part of one up to the next `${` or
+    /// after a closing `}`, processing escapes as it goes.
     fn string(&mut self) -> Token {
-        while self.peek() != '"' && !self.is_finished() {
-            if self.peek() == '\n' {
-                self.line += 1
+        let content_start = self.current;
+        // Only built once an escape means the value differs from the source.
+        let mut escaped: Option<String> = None;
+        loop {
+            if self.is_finished() {
+                self.errors.error(
+                    ErrorCode::Scan,
+                    self.span(),
+                    "Unterminated string.".to_string(),
+                );
+                return self.create_token(TokenType::Error);
+            }
+            let content_end = self.current;
+            match self.advance() {
+                '"' => {
+                    return self.string_token(
+                        TokenType::String,
+                        escaped,
+                        content_start,
+                        content_end,
+                    );
+                }
+                '$' if self.match_char('{') => {
+                    self.interpolations.push(0);
+                    return self.string_token(
+                        TokenType::Interpolation,
+                        escaped,
+                        content_start,
+                        content_end,
+                    );
+                }
+                '\\' => {
+                    let value = escaped
+                        .get_or_insert_with(|| self.code[content_start..content_end].to_string());
+                    if let Some(c) = self.escape() {
+                        value.push(c);
+                    }
+                }
+                c => {
+                    if c == '\n' {
+                        self.new_line();
+                    }
+                    if let Some(value) = &mut escaped {
+                        value.push(c);
+                    }
+                }
             }
+        }
+    }
+
+    /// A string token's lexeme is its value rather than its source text.
+    fn string_token(
+        &mut self,
+        token_type: TokenType,
+        escaped: Option<String>,
+        start: usize,
+        end: usize,
+    ) -> Token {
+        let value = match escaped {
+            Some(value) => self.symbols.intern(&value),
+            None => self.symbols.intern(&self.code[start..end]),
+        };
+        create_token(token_type, value, self.span())
+    }
+
+    /// Reads the escape after a `\\`, reporting it if it is invalid.
+    fn escape(&mut self) -> Option<char> {
+        let span_start = Span {
+            start: self.current - 1,
+            end: self.current,
+            line: self.line,
+            column: self.column - 1,
+        };
+        let c = match self.advance() {
+            'n' => Some('\n'),
+            't' => Some('\t'),
+            'r' => Some('\r'),
+            '"' => Some('"'),
+            '\\' => Some('\\'),
+            '$' => Some('$'),
+            'u' => self.unicode_escape(),
+            '\n' => {
+                self.new_line();
+                None
+            }
+            _ => None,
+        };
+        if c.is_none() {
+            let span = Span {
+                end: self.current,
+                ..span_start
+            };
+            self.errors.error(
+                ErrorCode::Scan,
+                span,
+                "Invalid escape sequence.".to_string(),
+            );
+        }
+        c
+    }
+
+    /// Reads the `{XXXX}` of a `\\u{XXXX}` escape: one to six hex digits
+    /// naming a Unicode scalar value.
+    fn unicode_escape(&mut self) -> Option<char> {
+        if !self.match_char('{') {
+            return None;
+        }
+        let digits_start = self.current;
+        while self.peek().is_ascii_hexdigit() {
             self.advance();
         }
-        self.advance();
-        let value: String = self.code[self.start + 1..self.current - 1].to_string();
-        return self.create_token(TokenType::String(value));
+        let digits = &self.code[digits_start..self.current];
+        let value = match digits.len() {
+            1..=6 => u32::from_str_radix(digits, 16)
+                .ok()
+                .and_then(char::from_u32),
+            _ => None,
+        };
+        if !self.match_char('}') {
+            return None;
+        }
+        value
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_tokens_carry_spans() {
+        let code = "var a = 1;\n  print \"x\ny\" + a;";
+        let tokens: Vec<Token> = Scanner::new(code.to_string(), Lox::new()).collect();
+        let print = &tokens[5];
+        assert_eq!(print.lexeme.as_str(), "print");
+        assert_eq!((print.span.line, print.span.column), (2, 3));
+        let string = &tokens[6];
+        assert_eq!(&code[string.span.start..string.span.end], "\"x\ny\"");
+        assert_eq!((string.span.line, string.span.column), (2, 9));
+        let plus = &tokens[7];
+        assert_eq!((plus.span.line, plus.span.column), (3, 4));
+    }
+
+    #[test]
+    fn test_unicode_text_and_identifiers() {
+        let code = "// コメント\nvar größe_2 = \"héllo, 世界\"; größe_2";
+        let mut scanner = Scanner::new(code.to_string(), Lox::new());
+        let tokens: Vec<Token> = scanner.by_ref().collect();
+        assert!(!scanner.get_errors().had_error);
+
+        assert_eq!(tokens[1].token_type, TokenType::Identifier);
+        assert_eq!(tokens[1].lexeme.as_str(), "größe_2");
+        assert_eq!(tokens[3].token_type, TokenType::String);
+        assert_eq!(tokens[3].lexeme.as_str(), "héllo, 世界");
+        assert_eq!((tokens[3].span.line, tokens[3].span.column), (2, 15));
+        assert_eq!((tokens[4].span.line, tokens[4].span.column), (2, 26));
+        assert_eq!(tokens[5].lexeme.as_str(), "größe_2");
+    }
+
+    #[test]
+    fn test_unterminated_string_reports_error() {
+        let mut scanner = Scanner::new("\"日本".to_string(), Lox::new());
+        scanner.by_ref().for_each(drop);
+        let errors = scanner.get_errors();
+        assert_eq!(errors.errors.len(), 1);
+        assert_eq!(errors.errors[0].message, "Unterminated string.");
+    }
+
+    #[test]
+    fn test_keywords_file_with_block_comments() {
+        let code = include_str!("../lox_src/keywords.lox");
+        let mut scanner = Scanner::new(code.to_string(), Lox::new());
+        let tokens: Vec<Token> = scanner.by_ref().collect();
+        assert!(!scanner.get_errors().had_error);
+
+        let lexemes: Vec<&str> = tokens.iter().map(|token| token.lexeme.as_str()).collect();
+        assert_eq!(
+            lexemes,
+            vec![
+                "if", "else", "var", "fn", "var", "myVar", "fn", "myFunc", "(", ")", "class",
+                "false", "true", "nil", "print", "return", "super", "this", ""
+            ]
+        );
+        let eof = tokens.last().unwrap();
+        assert_eq!(eof.token_type, TokenType::EOF);
+        assert_eq!(eof.span.line, 18);
+    }
+
+    #[test]
+    fn test_nested_block_comments() {
+        let code = "/* a * b / c */ x /* outer /* inner\n */ still */\ny";
+        let mut scanner = Scanner::new(code.to_string(), Lox::new());
+        let tokens: Vec<Token> = scanner.by_ref().collect();
+        assert!(!scanner.get_errors().had_error);
+
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(tokens[0].lexeme.as_str(), "x");
+        assert_eq!(tokens[1].lexeme.as_str(), "y");
+        assert_eq!((tokens[1].span.line, tokens[1].span.column), (3, 1));
+    }
+
+    #[test]
+    fn test_unterminated_block_comment_reports_start_line() {
+        let code = "print 1;\n/* one /* two */\n\n";
+        let mut scanner = Scanner::new(code.to_string(), Lox::new());
+        let tokens: Vec<TokenType> = scanner.by_ref().map(|token| token.token_type).collect();
+        assert_eq!(tokens.last(), Some(&TokenType::EOF));
+
+        let errors = scanner.get_errors();
+        assert_eq!(errors.errors.len(), 1);
+        assert_eq!(errors.errors[0].message, "Unterminated block comment.");
+        assert_eq!(errors.errors[0].span.line, 2);
+        assert_eq!(tokens.len(), 4);
+    }
+
+    #[test]
+    fn test_escape_sequences() {
+        let code = r#""a\tb\n\"q\" \\ \u{e9}\u{1F600} \$""#;
+        let tokens: Vec<Token> = Scanner::new(code.to_string(), Lox::new()).collect();
+        assert_eq!(tokens[0].token_type, TokenType::String);
+        assert_eq!(tokens[0].lexeme.as_str(), "a\tb\n\"q\" \\ é😀 $");
+        assert_eq!(&code[tokens[0].span.start..tokens[0].span.end], code);
+
+        for bad in [r#""\q""#, r#""\u{}""#, r#""\u{110000}""#, r#""\u{41""#] {
+            let mut scanner = Scanner::new(bad.to_string(), Lox::new());
+            scanner.by_ref().for_each(drop);
+            let errors = scanner.get_errors();
+            assert_eq!(
+                errors.errors[0].message, "Invalid escape sequence.",
+                "for {}",
+                bad
+            );
+        }
+    }
+
+    #[test]
+    fn test_invalid_escape_of_newline_counts_the_line() {
+        let mut scanner = Scanner::new("\"a\\\nb\"\n  x".to_string(), Lox::new());
+        let tokens: Vec<Token> = scanner.by_ref().collect();
+        assert_eq!(tokens[1].lexeme.as_str(), "x");
+        assert_eq!((tokens[1].span.line, tokens[1].span.column), (3, 3));
+        let errors = scanner.get_errors();
+        assert_eq!(errors.errors[0].message, "Invalid escape sequence.");
+        assert_eq!(errors.errors[0].span.line, 1);
+    }
+
+    #[test]
+    fn test_interpolation_tokens() {
+        let code = r#""a ${ {x} } b ${y}c""#;
+        let tokens: Vec<Token> = Scanner::new(code.to_string(), Lox::new()).collect();
+        let types: Vec<(TokenType, &str)> = tokens
+            .iter()
+            .map(|token| (token.token_type, token.lexeme.as_str()))
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                (TokenType::Interpolation, "a "),
+                (TokenType::LeftBrace, "{"),
+                (TokenType::Identifier, "x"),
+                (TokenType::RightBrace, "}"),
+                (TokenType::Interpolation, " b "),
+                (TokenType::Identifier, "y"),
+                (TokenType::String, "c"),
+                (TokenType::EOF, ""),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_numeric_literals() {
+        let code = "0xFF 0b1010 1_000_000 1e9 2.5E-3 1.5 9007199254740993 7.e";
+        let tokens: Vec<Token> = Scanner::new(code.to_string(), Lox::new()).collect();
+        let types: Vec<TokenType> = tokens[..10].iter().map(|token| token.token_type).collect();
+        assert_eq!(
+            types,
+            vec![
+                TokenType::Integer(255),
+                TokenType::Integer(10),
+                TokenType::Integer(1_000_000),
+                TokenType::Number(1e9),
+                TokenType::Number(2.5e-3),
+                TokenType::Number(1.5),
+                TokenType::Integer(9007199254740993),
+                TokenType::Integer(7),
+                TokenType::Dot,
+                TokenType::Identifier,
+            ]
+        );
+        assert_eq!(tokens[9].lexeme.as_str(), "e");
+
+        for bad in [
+            "0x",
+            "0b_",
+            "1_",
+            "1__0",
+            "1_e5",
+            "1_.5",
+            "1.5_",
+            "0x_FF",
+            "0xFF_",
+            "9223372036854775808",
+        ] {
+            let mut scanner = Scanner::new(bad.to_string(), Lox::new());
+            scanner.by_ref().for_each(drop);
+            assert!(
+                scanner.get_errors().had_error,
+                "expected an error for {}",
+                bad
+            );
+        }
+
+        let tokens: Vec<Token> =
+            Scanner::new("0xF_F 1_0.2_5e1_0".to_string(), Lox::new()).collect();
+        assert_eq!(tokens[0].token_type, TokenType::Integer(255));
+        assert_eq!(tokens[1].token_type, TokenType::Number(10.25e10));
+    }
+
+    #[test]
+    fn test_scans_lazily() {
+        let mut scanner = Scanner::new("print 1; @".to_string(), Lox::new());
+        let first: Vec<Token> = scanner.by_ref().take(3).collect();
+        assert_eq!(first[2].token_type, TokenType::Semicolon);
+        assert!(!scanner.get_errors().had_error);
+
+        let rest: Vec<TokenType> = scanner.by_ref().map(|token| token.token_type).collect();
+        assert_eq!(rest, vec![TokenType::Error, TokenType::EOF]);
+        assert!(scanner.get_errors().had_error);
+        assert!(scanner.next().is_none());
+    }
+
+    #[test]
+    fn test_fixed_lexemes_match_source_text() {
+        let code = "( ) { } , . - + ; / * ! != = == > >= < <= and class else false fn for if nil or print return super this true var while";
+        let tokens: Vec<Token> = Scanner::new(code.to_string(), Lox::new()).collect();
+        for (token, text) in tokens.iter().zip(code.split(' ')) {
+            assert_eq!(token.lexeme, Symbol::from(text));
+            assert_eq!(token.lexeme.as_str(), text);
+        }
+        assert_eq!(tokens.len(), 36);
+    }
+
+    #[test]
+    fn test_repeated_text_shares_one_symbol() {
+        let tokens: Vec<Token> = Scanner::new("x + x \"x\";".to_string(), Lox::new()).collect();
+        let texts: Vec<&str> = tokens.iter().map(|token| token.lexeme.as_str()).collect();
+        assert!(std::ptr::eq(texts[0], texts[2]));
+        assert!(std::ptr::eq(texts[0], texts[3]));
+    }
+
+    /// Scanning throughput on a large generated script. Run with
+    /// `cargo test --release -- --ignored --nocapture bench_scan`.
+    #[test]
+    #[ignore]
+    fn bench_scan() {
+        println!(
+            "token {} type {} sym {}",
+            std::mem::size_of::<Token>(),
+            std::mem::size_of::<TokenType>(),
+            std::mem::size_of::<Symbol>()
+        );
+        let unit = "class Point < Base {
+  init(x, y) { this.x = x; this.y = y; }
+  // Distance squared from the origin.
+  norm() { return this.x * this.x + this.y * this.y; }
+}
+fn label(p, name) { return \"point ${name}: (${p.x}, ${p.y})\"; }
+var total = 0;
+for (var i = 0; i < 1_000; i = i + 1) {
+  var p = Point(i, 2.5e3);
+  if (p.norm() >= 0xFF and !(i == 7)) total = total + p.norm(); else print label(p, \"n\");
+}
+";
+        let code = unit.repeat(20_000);
+        let start = std::time::Instant::now();
+        let mut tokens = 0;
+        for _ in 0..5 {
+            tokens += Scanner::new(code.clone(), Lox::new()).count();
+        }
+        let elapsed = start.elapsed();
+        println!(
+            "scanned {} tokens ({} MB) in {:?}: {:.1} MB/s",
+            tokens,
+            5 * code.len() / 1_000_000,
+            elapsed,
+            (5 * code.len()) as f64 / 1e6 / elapsed.as_secs_f64()
+        );
     }
 }
@@ -1,7 +1,106 @@
-use core::fmt::{Display, Formatter, Result};
-use std::str::FromStr;
+use core::fmt::{Debug, Display, Formatter, Result};
+use std::{
+    borrow::Borrow,
+    collections::HashSet,
+    hash::{Hash, Hasher},
+    rc::Rc,
+    str::FromStr,
+};
 
-#[derive(Debug, Clone, PartialEq)]
+/// An immutable string. Fixed texts such as keywords are used in place;
+/// any other text is shared by reference count, and two symbols from the
+/// same `Interner` compare by pointer.
+#[derive(Clone)]
+pub struct Symbol(Text);
+
+/// `Fixed` holds a thin pointer so a symbol stays two words wide.
+#[derive(Clone)]
+enum Text {
+    Fixed(&'static &'static str),
+    Shared(Rc<str>),
+}
+
+impl Symbol {
+    /// The lexeme of `EOF` and other tokens with no text.
+    pub const EMPTY: Symbol = Symbol::fixed(&"");
+
+    pub const fn fixed(text: &'static &'static str) -> Symbol {
+        Symbol(Text::Fixed(text))
+    }
+
+    pub fn as_str(&self) -> &str {
+        match &self.0 {
+            Text::Fixed(text) => text,
+            Text::Shared(text) => text,
+        }
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(text: &str) -> Symbol {
+        Symbol(Text::Shared(text.into()))
+    }
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Symbol) -> bool {
+        match (&self.0, &other.0) {
+            (Text::Shared(a), Text::Shared(b)) if Rc::ptr_eq(a, b) => true,
+            _ => self.as_str() == other.as_str(),
+        }
+    }
+}
+
+impl Eq for Symbol {}
+
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+impl Borrow<str> for Symbol {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Debug for Symbol {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+impl Display for Symbol {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Hands out one `Symbol` per distinct text. Each scan owns its own, so the
+/// table goes away with the scanner and a text lives only as long as some
+/// token or tree node still holds it.
+#[derive(Default)]
+pub struct Interner {
+    symbols: HashSet<Symbol>,
+}
+
+impl Interner {
+    pub fn new() -> Interner {
+        Interner::default()
+    }
+
+    pub fn intern(&mut self, text: &str) -> Symbol {
+        if let Some(symbol) = self.symbols.get(text) {
+            return symbol.clone();
+        }
+        let symbol = Symbol::from(text);
+        self.symbols.insert(symbol.clone());
+        symbol
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TokenType {
     LeftParen,
     RightParen,
@@ -24,8 +123,13 @@ pub enum TokenType {
     Less,
     LessEqual,
 
-    Identifier(String),
-    String(String),
+    /// The name is the token's lexeme.
+    Identifier,
+    /// The value, with escapes processed, is the token's lexeme.
+    String,
+    /// The part of a string literal before a `${`, as the lexeme.
+    Interpolation,
+    Integer(i64),
     Number(f64),
 
     And,
@@ -45,10 +149,12 @@ pub enum TokenType {
     Var,
     While,
 
+    #[allow(clippy::upper_case_acronyms)]
     EOF,
     Error,
 }
 
+/// Parses a keyword; any other word is an identifier.
 impl FromStr for TokenType {
     type Err = ();
 
@@ -70,87 +176,122 @@ impl FromStr for TokenType {
             "this" => Ok(TokenType::This),
             "var" => Ok(TokenType::Var),
             "while" => Ok(TokenType::While),
-            _ => Ok(TokenType::Identifier(s.to_string())),
+            _ => Ok(TokenType::Identifier),
         }
     }
 }
 
-impl Display for TokenType {
-    fn fmt(&self, f: &mut Formatter) -> Result {
-        write!(f, "{:?}", self)
+impl TokenType {
+    /// The lexeme of a punctuation or keyword token, which is always the same.
+    pub fn fixed_lexeme(&self) -> Option<&'static &'static str> {
+        let text = match self {
+            TokenType::LeftParen => &"(",
+            TokenType::RightParen => &")",
+            TokenType::LeftBrace => &"{",
+            TokenType::RightBrace => &"}",
+            TokenType::Comma => &",",
+            TokenType::Dot => &".",
+            TokenType::Minus => &"-",
+            TokenType::Plus => &"+",
+            TokenType::Semicolon => &";",
+            TokenType::Slash => &"/",
+            TokenType::Star => &"*",
+            TokenType::Bang => &"!",
+            TokenType::BangEqual => &"!=",
+            TokenType::Equal => &"=",
+            TokenType::EqualEqual => &"==",
+            TokenType::Greater => &">",
+            TokenType::GreaterEqual => &">=",
+            TokenType::Less => &"<",
+            TokenType::LessEqual => &"<=",
+            TokenType::And => &"and",
+            TokenType::Class => &"class",
+            TokenType::Else => &"else",
+            TokenType::False => &"false",
+            TokenType::Fn => &"fn",
+            TokenType::For => &"for",
+            TokenType::If => &"if",
+            TokenType::Nil => &"nil",
+            TokenType::Or => &"or",
+            TokenType::Print => &"print",
+            TokenType::Return => &"return",
+            TokenType::Super => &"super",
+            TokenType::This => &"this",
+            TokenType::True => &"true",
+            TokenType::Var => &"var",
+            TokenType::While => &"while",
+            _ => return None,
+        };
+        Some(text)
     }
 }
 
-pub struct Tokens(Vec<Token>);
-
-impl Tokens {
-    pub fn new() -> Tokens {
-        Tokens(Vec::new())
-    }
-
-    pub fn push(&mut self, token: Token) {
-        self.0.push(token)
-    }
-
-    pub fn clone(&self) -> Tokens {
-        Tokens(self.0.clone())
+impl Display for TokenType {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{:?}", self)
     }
 }
 
-impl Display for Tokens {
-    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-        self.0.iter().fold(Ok(()), |result, token| {
-            result.and_then(|_| writeln!(f, "{}", token))
-        })
-    }
+/// A region of the source. `start` and `end` are byte offsets; `line` and
+/// `column` locate `start` and are both 1-based.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
 }
 
-impl std::ops::Index<usize> for Tokens {
-    type Output = Token;
-    fn index(&self, index: usize) -> &Self::Output {
-        &self.0[index]
+impl Span {
+    /// The smallest span covering both `self` and `other`.
+    pub fn to(self, other: Span) -> Span {
+        let (first, last) = if self.start <= other.start {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        Span {
+            end: first.end.max(last.end),
+            ..first
+        }
     }
 }
 
 #[derive(Clone, Debug)]
 pub struct Token {
     pub token_type: TokenType,
-    pub lexeme: String,
-    pub line: usize,
+    pub lexeme: Symbol,
+    pub span: Span,
 }
 
 impl Token {
-    pub fn new(token_type: TokenType, lexeme: String, line: usize) -> Token {
+    pub fn new(token_type: TokenType, lexeme: Symbol, span: Span) -> Token {
         Token {
             token_type,
             lexeme,
-            line,
+            span,
         }
     }
-
-    pub fn get_lexeme(&self) -> String {
-        return self.lexeme.clone();
-    }
 }
 
 impl Display for Token {
     fn fmt(&self, f: &mut Formatter) -> Result {
-        write!(f, "{} {} {}", self.token_type, self.lexeme, self.line)
+        write!(f, "{} {} {}", self.token_type, self.lexeme, self.span.line)
     }
 }
 
 pub struct TokenBuilder {
     token_type: TokenType,
-    lexeme: String,
-    line: usize,
+    lexeme: Symbol,
+    span: Span,
 }
 
 impl TokenBuilder {
     pub fn new() -> TokenBuilder {
         TokenBuilder {
             token_type: TokenType::Error,
-            lexeme: "".to_string(),
-            line: 0,
+            lexeme: Symbol::EMPTY,
+            span: Span::default(),
         }
     }
 
@@ -158,7 +299,7 @@ impl TokenBuilder {
         Token {
             token_type: self.token_type,
             lexeme: self.lexeme,
-            line: self.line,
+            span: self.span,
         }
     }
 
@@ -167,13 +308,13 @@ impl TokenBuilder {
         self
     }
 
-    pub fn lexeme(mut self, lexeme: String) -> TokenBuilder {
+    pub fn lexeme(mut self, lexeme: Symbol) -> TokenBuilder {
         self.lexeme = lexeme;
         self
     }
 
-    pub fn line(mut self, line: usize) -> TokenBuilder {
-        self.line = line;
+    pub fn span(mut self, span: Span) -> TokenBuilder {
+        self.span = span;
         self
     }
 }
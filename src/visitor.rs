@@ -0,0 +1,217 @@
+use std::rc::Rc;
+
+use crate::ast::{Depth, Expr, FunctionDecl, Object, Stmt};
+use crate::token::{Span, Token};
+
+/// A pass over expressions producing an `R` per node. Use `walk_expr` to
+/// dispatch on a node; each method decides whether to visit its children.
+pub trait ExprVisitor<R> {
+    fn visit_assign(&mut self, name: &Token, value: &Expr, depth: &Depth) -> R;
+    fn visit_binary(&mut self, lhs: &Expr, op: &Token, rhs: &Expr) -> R;
+    fn visit_call(&mut self, callee: &Expr, paren: &Token, arguments: &[Expr]) -> R;
+    fn visit_get(&mut self, object: &Expr, name: &Token) -> R;
+    fn visit_grouping(&mut self, expr: &Expr) -> R;
+    fn visit_literal(&mut self, value: &Object, span: Span) -> R;
+    fn visit_logical(&mut self, lhs: &Expr, op: &Token, rhs: &Expr) -> R;
+    fn visit_set(&mut self, object: &Expr, name: &Token, value: &Expr) -> R;
+    fn visit_stringify(&mut self, expr: &Expr) -> R;
+    fn visit_super(&mut self, method: &Token, depth: &Depth) -> R;
+    fn visit_this(&mut self, keyword: &Token, depth: &Depth) -> R;
+    fn visit_unary(&mut self, op: &Token, rhs: &Expr) -> R;
+    fn visit_variable(&mut self, name: &Token, depth: &Depth) -> R;
+}
+
+/// A pass over statements producing an `R` per node.
+pub trait StmtVisitor<R> {
+    fn visit_block(&mut self, stmts: &[Stmt]) -> R;
+    fn visit_class(
+        &mut self,
+        name: &Token,
+        superclass: Option<&Expr>,
+        methods: &[Rc<FunctionDecl>],
+    ) -> R;
+    fn visit_expression(&mut self, expr: &Expr) -> R;
+    fn visit_function(&mut self, declaration: &Rc<FunctionDecl>) -> R;
+    fn visit_if(&mut self, condition: &Expr, then_branch: &Stmt, else_branch: Option<&Stmt>) -> R;
+    fn visit_print(&mut self, expr: &Expr) -> R;
+    fn visit_return(&mut self, keyword: &Token, value: Option<&Expr>) -> R;
+    fn visit_var(&mut self, name: &Token, initializer: Option<&Expr>) -> R;
+    fn visit_while(&mut self, condition: &Expr, body: &Stmt) -> R;
+}
+
+pub fn walk_expr<R, V: ExprVisitor<R> + ?Sized>(visitor: &mut V, expr: &Expr) -> R {
+    match expr {
+        Expr::Assign(name, value, depth) => visitor.visit_assign(name, value, depth),
+        Expr::Binary(lhs, op, rhs) => visitor.visit_binary(lhs, op, rhs),
+        Expr::Call(callee, paren, arguments) => visitor.visit_call(callee, paren, arguments),
+        Expr::Get(object, name) => visitor.visit_get(object, name),
+        Expr::Grouping(expr) => visitor.visit_grouping(expr),
+        Expr::Literal(value, span) => visitor.visit_literal(value, *span),
+        Expr::Logical(lhs, op, rhs) => visitor.visit_logical(lhs, op, rhs),
+        Expr::Set(object, name, value) => visitor.visit_set(object, name, value),
+        Expr::Stringify(expr) => visitor.visit_stringify(expr),
+        Expr::Super(method, depth) => visitor.visit_super(method, depth),
+        Expr::This(keyword, depth) => visitor.visit_this(keyword, depth),
+        Expr::Unary(op, rhs) => visitor.visit_unary(op, rhs),
+        Expr::Variable(name, depth) => visitor.visit_variable(name, depth),
+    }
+}
+
+pub fn walk_stmt<R, V: StmtVisitor<R> + ?Sized>(visitor: &mut V, stmt: &Stmt) -> R {
+    match stmt {
+        Stmt::Block(stmts) => visitor.visit_block(stmts),
+        Stmt::Class(name, superclass, methods) => {
+            visitor.visit_class(name, superclass.as_deref(), methods)
+        }
+        Stmt::Expression(expr) => visitor.visit_expression(expr),
+        Stmt::Function(declaration) => visitor.visit_function(declaration),
+        Stmt::If(condition, then_branch, else_branch) => {
+            visitor.visit_if(condition, then_branch, else_branch.as_deref())
+        }
+        Stmt::Print(expr) => visitor.visit_print(expr),
+        Stmt::Return(keyword, value) => visitor.visit_return(keyword, value.as_deref()),
+        Stmt::Var(name, initializer) => visitor.visit_var(name, initializer.as_deref()),
+        Stmt::While(condition, body) => visitor.visit_while(condition, body),
+    }
+}
+
+/// A pass that rewrites expressions in place. Override `visit_expr_mut` to
+/// replace nodes, calling `walk_expr_mut` to keep descending.
+pub trait ExprVisitorMut {
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        walk_expr_mut(self, expr)
+    }
+}
+
+/// A pass that rewrites statements, and the expressions inside them, in place.
+pub trait StmtVisitorMut: ExprVisitorMut {
+    fn visit_stmt_mut(&mut self, stmt: &mut Stmt) {
+        walk_stmt_mut(self, stmt)
+    }
+}
+
+/// Visits each direct child of `expr`.
+pub fn walk_expr_mut<V: ExprVisitorMut + ?Sized>(visitor: &mut V, expr: &mut Expr) {
+    match expr {
+        Expr::Assign(_, value, _) => visitor.visit_expr_mut(value),
+        Expr::Binary(lhs, _, rhs) | Expr::Logical(lhs, _, rhs) => {
+            visitor.visit_expr_mut(lhs);
+            visitor.visit_expr_mut(rhs);
+        }
+        Expr::Call(callee, _, arguments) => {
+            visitor.visit_expr_mut(callee);
+            arguments
+                .iter_mut()
+                .for_each(|argument| visitor.visit_expr_mut(argument));
+        }
+        Expr::Get(object, _) => visitor.visit_expr_mut(object),
+        Expr::Grouping(expr) | Expr::Stringify(expr) | Expr::Unary(_, expr) => {
+            visitor.visit_expr_mut(expr)
+        }
+        Expr::Set(object, _, value) => {
+            visitor.visit_expr_mut(object);
+            visitor.visit_expr_mut(value);
+        }
+        Expr::Literal(..) | Expr::Super(..) | Expr::This(..) | Expr::Variable(..) => {}
+    }
+}
+
+/// Visits each direct child of `stmt`. Function bodies shared with a live
+/// `LoxFunction` are copied before being rewritten.
+pub fn walk_stmt_mut<V: StmtVisitorMut + ?Sized>(visitor: &mut V, stmt: &mut Stmt) {
+    match stmt {
+        Stmt::Block(stmts) => stmts
+            .iter_mut()
+            .for_each(|stmt| visitor.visit_stmt_mut(stmt)),
+        Stmt::Class(_, superclass, methods) => {
+            if let Some(superclass) = superclass {
+                visitor.visit_expr_mut(superclass);
+            }
+            methods
+                .iter_mut()
+                .for_each(|method| walk_function_mut(visitor, method));
+        }
+        Stmt::Expression(expr) | Stmt::Print(expr) => visitor.visit_expr_mut(expr),
+        Stmt::Function(declaration) => walk_function_mut(visitor, declaration),
+        Stmt::If(condition, then_branch, else_branch) => {
+            visitor.visit_expr_mut(condition);
+            visitor.visit_stmt_mut(then_branch);
+            if let Some(else_branch) = else_branch {
+                visitor.visit_stmt_mut(else_branch);
+            }
+        }
+        Stmt::Return(_, value) | Stmt::Var(_, value) => {
+            if let Some(value) = value {
+                visitor.visit_expr_mut(value);
+            }
+        }
+        Stmt::While(condition, body) => {
+            visitor.visit_expr_mut(condition);
+            visitor.visit_stmt_mut(body);
+        }
+    }
+}
+
+fn walk_function_mut<V: StmtVisitorMut + ?Sized>(
+    visitor: &mut V,
+    declaration: &mut Rc<FunctionDecl>,
+) {
+    Rc::make_mut(declaration)
+        .body
+        .iter_mut()
+        .for_each(|stmt| visitor.visit_stmt_mut(stmt));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::lox::Lox;
+    use crate::parser::{Parse, Parser};
+    use crate::scanner::Scanner;
+    use crate::token::TokenType;
+
+    fn parse(code: &str) -> Vec<Stmt> {
+        Parser::new(Scanner::new(code.to_string(), Lox::new()), Lox::new()).parse()
+    }
+
+    /// Folds `+` on two number literals.
+    struct Folder;
+
+    impl ExprVisitorMut for Folder {
+        fn visit_expr_mut(&mut self, expr: &mut Expr) {
+            walk_expr_mut(self, expr);
+            if let Expr::Binary(lhs, op, rhs) = expr {
+                if let (
+                    Expr::Literal(Object::Integer(a), _),
+                    TokenType::Plus,
+                    Expr::Literal(Object::Integer(b), _),
+                ) = (lhs.as_ref(), &op.token_type, rhs.as_ref())
+                {
+                    *expr = Expr::Literal(Object::Integer(a + b), expr.span());
+                }
+            }
+        }
+    }
+
+    impl StmtVisitorMut for Folder {}
+
+    #[test]
+    fn test_mutating_visitor_rewrites_nested_expressions() {
+        let mut stmts = parse("fn f() { return 1 + 2 + x; } print 1 + 2 + 3;");
+        stmts
+            .iter_mut()
+            .for_each(|stmt| Folder.visit_stmt_mut(stmt));
+
+        let Stmt::Function(f) = &stmts[0] else {
+            panic!("expected function");
+        };
+        let Stmt::Return(_, Some(value)) = &f.body[0] else {
+            panic!("expected return");
+        };
+        assert_eq!(value.to_string(), "(+ 3 x)");
+        let Stmt::Print(value) = &stmts[1] else {
+            panic!("expected print");
+        };
+        assert_eq!(value.to_string(), "6");
+    }
+}